@@ -1,3 +1,10 @@
+//! Hello world on a TM4C123 over SPI.
+//!
+//! The tm4c123x HAL implements embedded-hal 0.2, while display-interface-spi 0.5 takes an
+//! embedded-hal 1.0 `SpiDevice`. The SPI bus and pins are adapted with `embedded-hal-compat`
+//! and chip select is handled by `embedded-hal-bus`. Besides the HAL and cortex-m crates this
+//! needs `display-interface-spi = "0.5"`, `embedded-hal-bus = "0.3"`,
+//! `embedded-hal-compat = "0.13"` and `ssd1322_di` with the `embedded-hal-02` feature.
 #![no_std]
 #![no_main]
 
@@ -24,6 +31,8 @@ use {
         prelude::*,
         text::{Baseline, Text},
     },
+    embedded_hal_bus::spi::ExclusiveDevice,
+    embedded_hal_compat::{markers::ForwardOutputPin, ForwardCompat},
 };
 
 #[entry]
@@ -69,13 +78,17 @@ fn main() -> ! {
         &sc.power_control,
     );
 
+    // Adapt the embedded-hal 0.2 bus and pins to embedded-hal 1.0
     let cs = porta.pa3.into_push_pull_output();
-    let spi_interface = SPIInterface::new(spi, dc, cs);
+    let spi =
+        ExclusiveDevice::new_no_delay(spi.forward(), cs.forward::<ForwardOutputPin>()).unwrap();
+    let spi_interface = SPIInterface::new(spi, dc.forward::<ForwardOutputPin>());
 
     let mut disp = display::Ssd1322::new(spi_interface);
 
-    // reset and init
-    disp.reset(&mut res, &mut delay).unwrap();
+    // reset and init, the reset pin and delay are still embedded-hal 0.2 so this needs the
+    // `embedded-hal-02` feature
+    disp.reset_eh02(&mut res, &mut delay).unwrap();
     disp.init().unwrap();
     disp.clear(Gray4::new(0x00)).unwrap();
     disp.flush().unwrap();
@@ -0,0 +1,53 @@
+//! Renders a screen on the desktop without hardware.
+//!
+//! Run with `cargo run --example simulator --features simulator [output.png]`.
+use embedded_graphics::{
+    mono_font::{ascii::FONT_10X20, MonoTextStyle},
+    pixelcolor::Gray4,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle, RoundedRectangle},
+    text::Text,
+};
+use embedded_graphics_simulator::OutputSettingsBuilder;
+use ssd1322_di::display::Ssd1322;
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "simulator.png".into());
+
+    let mut disp = Ssd1322::simulated();
+    disp.init().unwrap();
+
+    for level in 0..16 {
+        Rectangle::new(Point::new(level * 16, 48), Size::new(16, 16))
+            .into_styled(PrimitiveStyle::with_fill(Gray4::new(level as u8)))
+            .draw(&mut disp)
+            .unwrap();
+    }
+    RoundedRectangle::with_equal_corners(
+        Rectangle::new(Point::new(2, 2), Size::new(252, 40)),
+        Size::new(6, 6),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(Gray4::new(10), 2))
+    .draw(&mut disp)
+    .unwrap();
+    Text::new(
+        "SSD1322 simulator",
+        Point::new(40, 27),
+        MonoTextStyle::new(&FONT_10X20, Gray4::WHITE),
+    )
+    .draw(&mut disp)
+    .unwrap();
+    disp.flush().unwrap();
+
+    let settings = OutputSettingsBuilder::new()
+        .scale(3)
+        .pixel_spacing(1)
+        .build();
+    disp.render()
+        .to_grayscale_output_image(&settings)
+        .save_png(&path)
+        .unwrap();
+    println!("wrote {}", path);
+}
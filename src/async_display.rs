@@ -0,0 +1,350 @@
+//! Async display module
+//!
+//! [`Ssd1322Async`] is the async twin of [`Ssd1322`](crate::display::Ssd1322) for interfaces
+//! implementing [`AsyncWriteOnlyDataCommand`]. Drawing is synchronous as it only touches the
+//! framebuffer, while the transfers to the display can be awaited.
+use crate::command::Command;
+use crate::config::{gray_scale_commands, Ssd1322Config};
+use crate::dirty::{DirtyRects, DirtyTracker};
+use crate::display::write_sequence;
+use crate::error::{Error, FlushStage};
+use crate::framebuffer::{BoundingBox, FrameBuffer};
+use crate::grayscale::GrayScaleTable;
+use crate::size::{DisplaySize, DisplaySize256x64};
+use crate::window::Window;
+use display_interface::{AsyncWriteOnlyDataCommand, DataFormat::U8};
+use embedded_graphics::{
+    draw_target::DrawTarget, geometry::OriginDimensions, pixelcolor::Gray4, prelude::*,
+    primitives::Rectangle, Pixel,
+};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::delay::DelayNs;
+
+/// Represents the SSD1322 Display driven over an async interface.
+pub struct Ssd1322Async<DI, SIZE: DisplaySize = DisplaySize256x64, D = DirtyRects<1>> {
+    display: DI,
+    framebuffer: FrameBuffer<SIZE, D>,
+    config: Ssd1322Config,
+}
+
+impl<DI: AsyncWriteOnlyDataCommand> Ssd1322Async<DI> {
+    /// Creates the SSD1322 Display for a 256x64 panel.
+    ///
+    /// The device needs to be reset before use.
+    pub fn new(display: DI) -> Self {
+        Self::with_size(display, DisplaySize256x64)
+    }
+}
+
+impl<DI: AsyncWriteOnlyDataCommand, SIZE: DisplaySize> Ssd1322Async<DI, SIZE> {
+    /// Creates the SSD1322 Display for the panel geometry `SIZE`.
+    ///
+    /// The device needs to be reset before use.
+    pub fn with_size(display: DI, _size: SIZE) -> Self {
+        Self::with_tracker(display)
+    }
+}
+
+impl<DI: AsyncWriteOnlyDataCommand, SIZE: DisplaySize, D: DirtyTracker> Ssd1322Async<DI, SIZE, D> {
+    /// Creates the SSD1322 Display with the panel geometry and dirty tracker given by the type
+    /// parameters.
+    ///
+    /// The device needs to be reset before use.
+    pub fn with_tracker(display: DI) -> Self {
+        Self {
+            display,
+            framebuffer: FrameBuffer::new(),
+            config: Ssd1322Config::default(),
+        }
+    }
+
+    /// Resets the display.
+    pub async fn reset<RST, DELAY>(&mut self, rst: &mut RST, delay: &mut DELAY) -> Result<(), Error>
+    where
+        RST: OutputPin,
+        DELAY: DelayNs,
+    {
+        rst.set_low().map_err(|_| Error::Reset)?;
+        delay.delay_ms(10).await;
+
+        rst.set_high().map_err(|_| Error::Reset)?;
+        delay.delay_ms(200).await;
+
+        Ok(())
+    }
+
+    /// Initializes the display with the current configuration, which is
+    /// [`Ssd1322Config::default`] unless set by [`init_with_config`](Self::init_with_config).
+    pub async fn init(&mut self) -> Result<(), Error> {
+        self.init_with_config(self.config).await
+    }
+
+    /// Initializes the display with `config` and keeps it as the current configuration.
+    pub async fn init_with_config(&mut self, config: Ssd1322Config) -> Result<(), Error> {
+        self.config = config;
+        self.framebuffer.reset_scroll();
+        self.framebuffer
+            .set_visible_rows((0, (SIZE::HEIGHT - 1) as u8));
+        for command in config.init_sequence::<SIZE>() {
+            self.send_command(command).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the configuration used by [`init`](Self::init).
+    pub fn config(&self) -> &Ssd1322Config {
+        &self.config
+    }
+
+    /// Consumes the display driver and returns the underlying display interface.
+    pub fn release(self) -> DI {
+        self.display
+    }
+
+    /// Returns the underlying display interface, e.g. to inspect a mock interface in tests.
+    pub fn interface(&self) -> &DI {
+        &self.display
+    }
+
+    /// Returns the underlying display interface mutably.
+    pub fn interface_mut(&mut self) -> &mut DI {
+        &mut self.display
+    }
+
+    /// Uploads a custom gray scale table and enables it.
+    pub async fn set_gray_scale_table(&mut self, table: &GrayScaleTable) -> Result<(), Error> {
+        for command in IntoIterator::into_iter(gray_scale_commands(Some(table))).flatten() {
+            self.send_command(command).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Allows to send custom commands to the display.
+    pub async fn send_command(&mut self, command: Command) -> Result<(), Error> {
+        let opcode = command.opcode();
+        command
+            .send_async(&mut self.display)
+            .await
+            .map_err(|source| Error::Command { opcode, source })
+    }
+
+    /// Flushes the entire display, and makes the output visible on the screen.
+    pub async fn flush_all(&mut self) -> Result<(), Error> {
+        let window = FrameBuffer::<SIZE, D>::full_window();
+        let column_range = SIZE::column_range(self.config.remaps().0);
+        for (segment, gddram_row) in self.framebuffer.segments(window) {
+            start_write(&mut self.display, &segment, column_range, gddram_row).await?;
+            self.display
+                .send_data(U8(self.framebuffer.rows_bytes(segment.rows)))
+                .await
+                .map_err(|source| Error::Flush {
+                    stage: FlushStage::Data,
+                    row: segment.rows.0,
+                    source,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes only the changed portions of the display.
+    ///
+    /// Each region recorded by the dirty tracker is sent as its own window, unless sending the
+    /// whole framebuffer is cheaper.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        let column_range = SIZE::column_range(self.config.remaps().0);
+        for window in self.framebuffer.dirty_windows() {
+            for (segment, gddram_row) in self.framebuffer.segments(window) {
+                start_write(&mut self.display, &segment, column_range, gddram_row).await?;
+
+                for i in segment.rows.0..=segment.rows.1 {
+                    self.display
+                        .send_data(U8(self.framebuffer.row_bytes(&segment, i)))
+                        .await
+                        .map_err(|source| Error::Flush {
+                            stage: FlushStage::Data,
+                            row: i,
+                            source,
+                        })?;
+                }
+            }
+        }
+
+        self.framebuffer.reset_dirty();
+
+        Ok(())
+    }
+}
+
+/// Sets the column and row address window and starts writing to the GDDRAM at row
+/// `gddram_row`.
+async fn start_write<DI: AsyncWriteOnlyDataCommand>(
+    display: &mut DI,
+    window: &Window,
+    column_range: (u8, u8),
+    gddram_row: u8,
+) -> Result<(), Error> {
+    for (command, stage) in write_sequence(window, column_range, gddram_row) {
+        command
+            .send_async(display)
+            .await
+            .map_err(|source| Error::Flush {
+                stage,
+                row: window.rows.0,
+                source,
+            })?;
+    }
+
+    Ok(())
+}
+
+impl<DI, SIZE: DisplaySize, D: DirtyTracker> BoundingBox for Ssd1322Async<DI, SIZE, D> {
+    fn update_box(&mut self, x: u16, y: u8) {
+        self.framebuffer.update_box(x, y)
+    }
+}
+
+impl<DI, SIZE: DisplaySize, D: DirtyTracker> DrawTarget for Ssd1322Async<DI, SIZE, D> {
+    type Color = Gray4;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let transposed = self.config.rotation().is_transposed();
+        self.framebuffer.draw_iter(pixels, transposed);
+
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let transposed = self.config.rotation().is_transposed();
+        self.framebuffer.fill_contiguous(area, colors, transposed);
+
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let transposed = self.config.rotation().is_transposed();
+        self.framebuffer.fill_solid(area, color, transposed);
+
+        Ok(())
+    }
+
+    fn clear(&mut self, fill: Self::Color) -> Result<(), Self::Error> {
+        self.framebuffer.clear(fill);
+
+        Ok(())
+    }
+}
+
+impl<DI, SIZE: DisplaySize, D> OriginDimensions for Ssd1322Async<DI, SIZE, D> {
+    fn size(&self) -> Size {
+        if self.config.rotation().is_transposed() {
+            Size::new(SIZE::HEIGHT.into(), SIZE::WIDTH.into())
+        } else {
+            Size::new(SIZE::WIDTH.into(), SIZE::HEIGHT.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+    use display_interface::{DataFormat, DisplayError};
+
+    extern crate std;
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    /// Waker that does nothing, in place of `Waker::noop` which needs Rust 1.85.
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Polls a future that never waits on anything to completion.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut context = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    /// Counts the commands and data bytes sent.
+    #[derive(Default)]
+    pub struct TestInterface {
+        commands: usize,
+        data_bytes: usize,
+    }
+
+    impl AsyncWriteOnlyDataCommand for TestInterface {
+        async fn send_commands(&mut self, _cmds: DataFormat<'_>) -> Result<(), DisplayError> {
+            self.commands += 1;
+            Ok(())
+        }
+
+        async fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+            match buf {
+                U8(slice) => {
+                    self.data_bytes += slice.len();
+                    Ok(())
+                }
+                _ => Err(DisplayError::DataFormatNotImplemented),
+            }
+        }
+    }
+
+    #[test]
+    fn init_sends_sequence() {
+        let mut disp = Ssd1322Async::new(TestInterface::default());
+        block_on(disp.init()).unwrap();
+
+        let iface = disp.release();
+        assert_eq!(iface.commands, 22);
+    }
+
+    #[test]
+    fn flush_sends_changed_window() {
+        let mut disp = Ssd1322Async::new(TestInterface::default());
+
+        Pixel(Point::new(10, 5), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+        Pixel(Point::new(13, 6), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+        assert_eq!(disp.framebuffer.bounding_box(), Some(([5, 6], [5, 6])));
+
+        block_on(disp.flush()).unwrap();
+        assert!(disp.framebuffer.bounding_box().is_none());
+
+        // Column, row address and 2 rows of 4 bytes
+        let iface = disp.release();
+        assert_eq!(iface.commands, 3);
+        assert_eq!(iface.data_bytes, 4 + 8);
+    }
+
+    #[test]
+    fn flush_all_sends_framebuffer() {
+        let mut disp = Ssd1322Async::new(TestInterface::default());
+        block_on(disp.flush_all()).unwrap();
+
+        let iface = disp.release();
+        assert_eq!(iface.data_bytes, 4 + 256 * 64 / 2);
+    }
+}
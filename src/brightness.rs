@@ -0,0 +1,77 @@
+//! Perceptual brightness
+//!
+//! The light output of the panel is roughly proportional to the master current (16 steps)
+//! times the contrast current (256 steps). A brightness level is mapped through the CIE 1976
+//! lightness curve to a target output, which is reached with the lowest master current that
+//! can produce it so that the contrast current keeps a fine resolution at low levels.
+
+/// Highest brightness level.
+pub const MAX_BRIGHTNESS: u8 = 255;
+
+/// Returns the `(master current, contrast current)` giving the perceptual brightness `level`.
+///
+/// Level 0 is the dimmest output of the panel, not off.
+pub fn currents(level: u8) -> (u8, u8) {
+    let lightness = f32::from(level) * 100.0 / f32::from(MAX_BRIGHTNESS);
+    let output = if lightness > 8.0 {
+        let t = (lightness + 16.0) / 116.0;
+        t * t * t
+    } else {
+        lightness / 903.3
+    };
+
+    let master = (libm::ceilf(output * 16.0) as u8).clamp(1, 16) - 1;
+    let contrast = output * 4096.0 / f32::from(master + 1) + 0.5;
+    let contrast = (contrast.clamp(1.0, 256.0) as u16 - 1) as u8;
+    (master, contrast)
+}
+
+/// Returns the highest perceptual brightness level whose currents give no more output than
+/// `master` and `contrast` currents.
+pub fn level(master: u8, contrast: u8) -> u8 {
+    let target = output(master, contrast);
+    let (mut low, mut high) = (0, MAX_BRIGHTNESS);
+    while low < high {
+        let mid = high - (high - low) / 2;
+        let (master, contrast) = currents(mid);
+        if output(master, contrast) <= target {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
+
+/// Light output of the currents in units of the lowest one.
+fn output(master: u8, contrast: u8) -> u32 {
+    (u32::from(master.min(15)) + 1) * (u32::from(contrast) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_range_is_covered() {
+        assert_eq!(currents(MAX_BRIGHTNESS), (15, 255));
+        assert_eq!(currents(0), (0, 0));
+    }
+
+    #[test]
+    fn output_increases_with_level() {
+        let output =
+            |(master, contrast): (u8, u8)| (u32::from(master) + 1) * (u32::from(contrast) + 1);
+        for level in 1..=MAX_BRIGHTNESS {
+            assert!(output(currents(level)) >= output(currents(level - 1)));
+        }
+    }
+
+    #[test]
+    fn level_inverts_currents() {
+        for level in [0, 20, 64, 128, 200, MAX_BRIGHTNESS] {
+            let (master, contrast) = currents(level);
+            assert_eq!(super::level(master, contrast), level);
+        }
+    }
+}
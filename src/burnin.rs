@@ -0,0 +1,205 @@
+//! Burn-in protection
+//!
+//! OLED pixels age with the time they are lit. [`BurnInProtection`] slowly moves the image up and
+//! down with the display offset so static content does not always light the same pixels, keeps
+//! track of how long each region of the panel has been lit, and turns on a screen saver once the
+//! application has reported no activity for a while. Time is reported by the application with
+//! [`Ssd1322::tick`](crate::display::Ssd1322::tick).
+use crate::mode::DisplayMode;
+use crate::power::PowerState;
+
+/// Number of on-time regions across the panel.
+pub const REGION_COLUMNS: usize = 8;
+
+/// Number of on-time regions down the panel.
+pub const REGION_ROWS: usize = 4;
+
+/// Largest pixel shift in rows, larger [`BurnInProtection::max_shift`] values are clamped to it.
+pub const MAX_SHIFT: u8 = 16;
+
+/// Lit time of each region in milliseconds at full gray level, indexed `[row][column]`.
+pub type OnTime = [[u32; REGION_COLUMNS]; REGION_ROWS];
+
+/// Settings of the burn-in protection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BurnInProtection {
+    /// Time between two pixel shifts in milliseconds.
+    pub shift_interval_ms: u32,
+    /// Largest shift in rows, at most [`MAX_SHIFT`]. The image moves down one row per shift up
+    /// to this value and back, 0 disables shifting.
+    ///
+    /// The GDDRAM rows above the image that move into view are written black, unless the panel
+    /// drives all 128 rows and the image wraps around instead.
+    pub max_shift: u8,
+    /// Time without activity after which the screen saver starts, `None` to disable it.
+    pub idle_timeout_ms: Option<u32>,
+    /// Power state used by the screen saver.
+    pub screen_saver: PowerState,
+}
+
+impl Default for BurnInProtection {
+    fn default() -> Self {
+        Self {
+            shift_interval_ms: 60_000,
+            max_shift: 2,
+            idle_timeout_ms: Some(300_000),
+            screen_saver: PowerState::Dim,
+        }
+    }
+}
+
+/// Running state of the burn-in protection.
+#[derive(Debug, Clone)]
+pub(crate) struct BurnIn {
+    pub(crate) settings: BurnInProtection,
+    since_shift_ms: u32,
+    shift_step: u16,
+    pub(crate) idle_ms: u32,
+    /// Power state to return to when the screen saver stops.
+    pub(crate) resume: Option<PowerState>,
+    /// Whether the GDDRAM rows moved into view by the shift have been written black.
+    pub(crate) margin_blank: bool,
+    pub(crate) on_time: OnTime,
+}
+
+impl BurnIn {
+    pub(crate) fn new(settings: BurnInProtection) -> Self {
+        Self {
+            settings: BurnInProtection {
+                max_shift: settings.max_shift.min(MAX_SHIFT),
+                ..settings
+            },
+            since_shift_ms: 0,
+            shift_step: 0,
+            idle_ms: 0,
+            resume: None,
+            margin_blank: false,
+            on_time: [[0; REGION_COLUMNS]; REGION_ROWS],
+        }
+    }
+
+    /// Restarts the shift and the screen saver after the controller has been initialized, which
+    /// moved the image back to the configured display offset and turned the display on. The
+    /// on-time is kept.
+    pub(crate) fn restart(&mut self) {
+        self.since_shift_ms = 0;
+        self.shift_step = 0;
+        self.idle_ms = 0;
+        self.resume = None;
+        self.margin_blank = false;
+    }
+
+    /// Advances the shift timer by `elapsed_ms`, returning the new shift in rows when it is time
+    /// to move the image.
+    pub(crate) fn next_shift(&mut self, elapsed_ms: u32) -> Option<u8> {
+        let max = u16::from(self.settings.max_shift);
+        if max == 0 {
+            return None;
+        }
+
+        self.since_shift_ms = self.since_shift_ms.saturating_add(elapsed_ms);
+        if self.since_shift_ms < self.settings.shift_interval_ms {
+            return None;
+        }
+        self.since_shift_ms = 0;
+
+        // Triangle wave 0, 1, .., max, .., 1, 0, ..
+        self.shift_step = (self.shift_step + 1) % (2 * max);
+        let shift = if self.shift_step <= max {
+            self.shift_step
+        } else {
+            2 * max - self.shift_step
+        };
+        Some(shift as u8)
+    }
+
+    /// Adds `elapsed_ms` of on-time to each region, weighted by its average gray level.
+    pub(crate) fn add_on_time(
+        &mut self,
+        buffer: &[u8],
+        row_bytes: usize,
+        mode: DisplayMode,
+        elapsed_ms: u32,
+    ) {
+        let height = buffer.len() / row_bytes;
+        for (region_row, on_time) in self.on_time.iter_mut().enumerate() {
+            let rows = region_row * height / REGION_ROWS..(region_row + 1) * height / REGION_ROWS;
+            for (region_column, on_time) in on_time.iter_mut().enumerate() {
+                let columns = region_column * row_bytes / REGION_COLUMNS
+                    ..(region_column + 1) * row_bytes / REGION_COLUMNS;
+                let pixels = (rows.len() * columns.len() * 2) as u64;
+                if pixels == 0 {
+                    continue;
+                }
+
+                let luma: u64 = match mode {
+                    DisplayMode::AllOn => 15 * pixels,
+                    DisplayMode::AllOff => 0,
+                    DisplayMode::Normal | DisplayMode::Inverse => {
+                        let sum: u64 = rows
+                            .clone()
+                            .flat_map(|row| &buffer[row * row_bytes..][columns.clone()])
+                            .map(|&byte| u64::from(byte >> 4) + u64::from(byte & 0x0F))
+                            .sum();
+                        if mode == DisplayMode::Inverse {
+                            15 * pixels - sum
+                        } else {
+                            sum
+                        }
+                    }
+                };
+                let lit_ms = u64::from(elapsed_ms) * luma / (15 * pixels);
+                *on_time = on_time.saturating_add(lit_ms as u32);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_follows_triangle_wave() {
+        let mut burn_in = BurnIn::new(BurnInProtection {
+            shift_interval_ms: 100,
+            max_shift: 2,
+            ..BurnInProtection::default()
+        });
+
+        assert_eq!(burn_in.next_shift(50), None);
+        let shifts = [(); 5].map(|_| burn_in.next_shift(100));
+        assert_eq!(shifts, [Some(1), Some(2), Some(1), Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn large_shifts_are_clamped() {
+        for max_shift in [MAX_SHIFT, 128, u8::MAX] {
+            let mut burn_in = BurnIn::new(BurnInProtection {
+                shift_interval_ms: 1,
+                max_shift,
+                ..BurnInProtection::default()
+            });
+            // A full period up to the largest shift and back to 0
+            let shifts = [(); 2 * MAX_SHIFT as usize].map(|_| burn_in.next_shift(1));
+            assert_eq!(shifts.iter().max(), Some(&Some(MAX_SHIFT)));
+            assert_eq!(shifts.last(), Some(&Some(0)));
+        }
+    }
+
+    #[test]
+    fn on_time_is_weighted_by_gray_level() {
+        let mut burn_in = BurnIn::new(BurnInProtection::default());
+        // 16x4 pixels, the top left region at full level, the next one at level 5
+        let mut buffer = [0u8; 8 * 4];
+        buffer[0] = 0xFF;
+        buffer[1] = 0x55;
+
+        burn_in.add_on_time(&buffer, 8, DisplayMode::Normal, 1000);
+        assert_eq!(burn_in.on_time[0][..3], [1000, 333, 0]);
+
+        burn_in.add_on_time(&buffer, 8, DisplayMode::Inverse, 1000);
+        assert_eq!(burn_in.on_time[0][..3], [1000, 999, 1000]);
+        assert_eq!(burn_in.on_time[3][7], 1000);
+    }
+}
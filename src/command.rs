@@ -1,37 +1,97 @@
+//! ssd1322 Commands
+use crate::config::{Enhancement, GpioMode, RemapFormat, Vdd, Vsl};
+#[cfg(feature = "async")]
+use display_interface::AsyncWriteOnlyDataCommand;
 use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
 
-/// ssd1322 Commands
-
-/// Commands - subset of the supported commands
-#[derive(Debug)]
-#[allow(dead_code)]
+/// Commands supported by the SSD1322.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Command {
-    Unlock,
+    /// Enables the gray scale table set by [`Command::SetGrayScaleTable`].
+    EnableGrayScaleTable,
+    /// Sets the start and end column address. One column address covers 4 pixels.
     SetColumnAddress(u8, u8),
+    /// Sets the start and end row address.
     SetRowAddress(u8, u8),
-    SetDisplayClock(u8),
-    SetMuxRatio(u8),
-    SetDisplayOffset(u8),
+    /// Enables writing the data that follows into the GDDRAM.
+    WriteRAM,
+    /// Enables reading data from the GDDRAM.
+    ReadRAM,
+    /// Sets the address increment, remaps and dual COM line mode.
+    SetRemapFormat(RemapFormat),
+    /// Sets the GDDRAM row shown on the first display line.
     SetStartLine(u8),
-    SetRemapFormat(u8, u8),
-    SetGPIO(u8),
-    SetFunctionSelection(u8),
-    SetDisplayEnhancementA(u8, u8),
-    SetContrastCurrent(u8),
-    SetMasterCurrent(u8),
-    SetLinearGrayScaleTable,
-    SetPhaseLength(u8),
-    SetDisplayEnhancementB(u8, u8),
-    SetPrechargeVoltage(u8),
-    SetPrechargePeriod(u8),
-    SetVCOMH(u8),
-    NormalDisplayMode,
-    AllPixelsOn,
+    /// Sets the vertical shift of the mapping from display lines to COM pins.
+    SetDisplayOffset(u8),
+    /// Turns all pixels off regardless of the GDDRAM contents (entire display off).
     AllPixelsOff,
+    /// Turns all pixels on at the highest gray level (entire display on).
+    AllPixelsOn,
+    /// Shows the GDDRAM contents.
+    NormalDisplayMode,
+    /// Shows the GDDRAM contents with inverted gray levels.
+    InverseDisplayMode,
+    /// Only drives the display rows between the start and end row.
+    EnablePartialDisplay(u8, u8),
+    /// Leaves the partial display mode.
     ExitPartialDisplay,
-    WriteRAM,
-    DisplayOn,
+    /// Selects the internal or external VDD.
+    SetFunctionSelection(Vdd),
+    /// Turns the display off (sleep mode on).
     DisplayOff,
+    /// Turns the display on (sleep mode off).
+    DisplayOn,
+    /// Sets the phase 1 (`2..=15`) and phase 2 (`3..=15`) periods in display clocks.
+    SetPhaseLength(u8, u8),
+    /// Sets the front clock divider, which divides by `2^divider` for `0..=10`, and the
+    /// oscillator frequency `0..=15`.
+    SetDisplayClock(u8, u8),
+    /// Selects the VSL source and the low gray scale display quality.
+    SetDisplayEnhancementA(Vsl, Enhancement),
+    /// Sets the modes of GPIO0 and GPIO1.
+    SetGPIO(GpioMode, GpioMode),
+    /// Sets the second pre-charge period.
+    SetSecondPrechargePeriod(u8),
+    /// Sets the gray scale pulse widths of GS1 to GS15.
+    SetGrayScaleTable([u8; 15]),
+    /// Selects the default linear gray scale table.
+    SetLinearGrayScaleTable,
+    /// Sets the pre-charge voltage level.
+    SetPrechargeVoltage(u8),
+    /// Sets the COM deselect voltage level.
+    SetVCOMH(u8),
+    /// Sets the contrast current.
+    SetContrastCurrent(u8),
+    /// Sets the master contrast current.
+    SetMasterCurrent(u8),
+    /// Sets the multiplex ratio.
+    SetMuxRatio(u8),
+    /// Sets Display Enhancement B.
+    SetDisplayEnhancementB(Enhancement),
+    /// Unlocks the command interface.
+    Unlock,
+    /// Locks the command interface, only [`Command::Unlock`] is accepted afterwards.
+    Lock,
+}
+
+/// Command byte followed by its data bytes.
+struct Encoded {
+    bytes: [u8; 16],
+    len: usize,
+}
+
+impl Encoded {
+    fn command(&self) -> &[u8] {
+        &self.bytes[0..1]
+    }
+
+    fn data(&self) -> Option<&[u8]> {
+        if self.len > 1 {
+            Some(&self.bytes[1..self.len])
+        } else {
+            None
+        }
+    }
 }
 
 impl Command {
@@ -40,47 +100,155 @@ impl Command {
     where
         DI: WriteOnlyDataCommand,
     {
-        let mut handle_command = |data: &[u8]| {
-            // Send command over the interface
-            let _ = iface.send_commands(DataFormat::U8(&data[0..1]));
+        let encoded = self.encode();
+
+        // Send command over the interface
+        iface.send_commands(DataFormat::U8(encoded.command()))?;
+
+        // If the command has any data portion then send that also
+        if let Some(data) = encoded.data() {
+            iface.send_data(DataFormat::U8(data))?;
+        }
+
+        Ok(())
+    }
+
+    /// Send command to ssd1322 over an async interface
+    #[cfg(feature = "async")]
+    pub async fn send_async<DI>(self, iface: &mut DI) -> Result<(), DisplayError>
+    where
+        DI: AsyncWriteOnlyDataCommand,
+    {
+        let encoded = self.encode();
+
+        // Send command over the interface
+        iface
+            .send_commands(DataFormat::U8(encoded.command()))
+            .await?;
+
+        // If the command has any data portion then send that also
+        if let Some(data) = encoded.data() {
+            iface.send_data(DataFormat::U8(data)).await?;
+        }
+
+        Ok(())
+    }
 
-            // If the command has any data portion then send that also
-            if data.len() > 1 {
-                let _ = iface.send_data(DataFormat::U8(&data[1..data.len()]));
+    fn encode(self) -> Encoded {
+        let handle_command = |data: &[u8]| {
+            let mut bytes = [0; 16];
+            bytes[..data.len()].copy_from_slice(data);
+            Encoded {
+                bytes,
+                len: data.len(),
             }
         };
 
         match self {
-            // Set command unlock
-            Command::Unlock => handle_command(&[0xFD, 0x12]),
+            // Enable gray scale table
+            Command::EnableGrayScaleTable => handle_command(&[0x00]),
 
             // Set the bounding box
             Command::SetColumnAddress(a, b) => handle_command(&[0x15, a, b]),
             Command::SetRowAddress(a, b) => handle_command(&[0x75, a, b]),
 
-            // Set the divide and osc freq
-            Command::SetDisplayClock(a) => handle_command(&[0xB3, a]),
+            // Write the data following this command
+            Command::WriteRAM => handle_command(&[0x5C]),
 
-            // Set the Multiplex ratio
-            Command::SetMuxRatio(a) => handle_command(&[0xCA, a]),
+            // Read the data following this command
+            Command::ReadRAM => handle_command(&[0x5D]),
 
-            // Shift mapping RAM counter
-            Command::SetDisplayOffset(a) => handle_command(&[0xA2, a]),
+            // Set horizontal address increment
+            Command::SetRemapFormat(format) => {
+                let (a, b) = format.bytes();
+                handle_command(&[0xA0, a, b])
+            }
 
             // Shift mapping RAM display start line
             Command::SetStartLine(a) => handle_command(&[0xA1, a]),
 
-            // Set horizontal address increment
-            Command::SetRemapFormat(a, b) => handle_command(&[0xA0, a, b]),
+            // Shift mapping RAM counter
+            Command::SetDisplayOffset(a) => handle_command(&[0xA2, a]),
 
-            // GPIO pins
-            Command::SetGPIO(a) => handle_command(&[0xB5, a]),
+            // Set all pixels off
+            Command::AllPixelsOff => handle_command(&[0xA4]),
+
+            // Set all pixels on
+            Command::AllPixelsOn => handle_command(&[0xA5]),
+
+            // Set normal display mode
+            Command::NormalDisplayMode => handle_command(&[0xA6]),
+
+            // Set inverse display mode
+            Command::InverseDisplayMode => handle_command(&[0xA7]),
+
+            // Enable partial display
+            Command::EnablePartialDisplay(a, b) => handle_command(&[0xA8, a, b]),
+
+            // Exit partial display
+            Command::ExitPartialDisplay => handle_command(&[0xA9]),
 
             // Function selection
-            Command::SetFunctionSelection(a) => handle_command(&[0xAB, a]),
+            Command::SetFunctionSelection(vdd) => {
+                let a = match vdd {
+                    Vdd::External => 0x00,
+                    Vdd::Internal => 0x01,
+                };
+                handle_command(&[0xAB, a])
+            }
+
+            // Sleep mode on
+            Command::DisplayOff => handle_command(&[0xAE]),
+
+            // Sleep mode off
+            Command::DisplayOn => handle_command(&[0xAF]),
+
+            // Set phase length
+            Command::SetPhaseLength(phase1, phase2) => {
+                handle_command(&[0xB1, (phase2 << 4) | (phase1 & 0x0F)])
+            }
+
+            // Set the divide and osc freq
+            Command::SetDisplayClock(divider, frequency) => {
+                handle_command(&[0xB3, (frequency << 4) | (divider & 0x0F)])
+            }
 
             // Set Display Enhancement A
-            Command::SetDisplayEnhancementA(a, b) => handle_command(&[0xB4, a, b]),
+            Command::SetDisplayEnhancementA(vsl, quality) => {
+                let a = match vsl {
+                    Vsl::External => 0xA0,
+                    Vsl::Internal => 0xA2,
+                };
+                let b = match quality {
+                    Enhancement::Normal => 0xB5,
+                    Enhancement::Enhanced => 0xFD,
+                };
+                handle_command(&[0xB4, a, b])
+            }
+
+            // GPIO pins
+            Command::SetGPIO(gpio0, gpio1) => {
+                handle_command(&[0xB5, (gpio1.bits() << 2) | gpio0.bits()])
+            }
+
+            // Set second pre-charge period
+            Command::SetSecondPrechargePeriod(a) => handle_command(&[0xB6, a]),
+
+            // Set gray scale table
+            Command::SetGrayScaleTable(table) => {
+                let mut data = [0xB8; 16];
+                data[1..].copy_from_slice(&table);
+                handle_command(&data)
+            }
+
+            // Set linear gray scale table
+            Command::SetLinearGrayScaleTable => handle_command(&[0xB9]),
+
+            // Set pre-charge voltage
+            Command::SetPrechargeVoltage(a) => handle_command(&[0xBB, a]),
+
+            // Set common pins voltage level
+            Command::SetVCOMH(a) => handle_command(&[0xBE, a]),
 
             // Set Contrast current
             Command::SetContrastCurrent(a) => handle_command(&[0xC1, a]),
@@ -88,46 +256,195 @@ impl Command {
             // Set Master current
             Command::SetMasterCurrent(a) => handle_command(&[0xC7, a]),
 
-            // Set linear gray scale table
-            Command::SetLinearGrayScaleTable => handle_command(&[0xB9]),
-
-            // Set phase length
-            Command::SetPhaseLength(a) => handle_command(&[0xB1, a]),
+            // Set the Multiplex ratio
+            Command::SetMuxRatio(a) => handle_command(&[0xCA, a]),
 
             // Set Display Enhancement B
-            Command::SetDisplayEnhancementB(a, b) => handle_command(&[0xD1, a, b]),
+            Command::SetDisplayEnhancementB(enhancement) => {
+                let a = match enhancement {
+                    Enhancement::Normal => 0xA2,
+                    Enhancement::Enhanced => 0x82,
+                };
+                handle_command(&[0xD1, a, 0x20])
+            }
 
-            // Set pre-charge voltage
-            Command::SetPrechargeVoltage(a) => handle_command(&[0xBB, a]),
+            // Set command unlock
+            Command::Unlock => handle_command(&[0xFD, 0x12]),
+
+            // Set command lock
+            Command::Lock => handle_command(&[0xFD, 0x16]),
+        }
+    }
 
-            // Set pre-charge period
-            Command::SetPrechargePeriod(a) => handle_command(&[0xB6, a]),
+    /// Returns the command byte sent for this command.
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Command::EnableGrayScaleTable => 0x00,
+            Command::SetColumnAddress(..) => 0x15,
+            Command::SetRowAddress(..) => 0x75,
+            Command::WriteRAM => 0x5C,
+            Command::ReadRAM => 0x5D,
+            Command::SetRemapFormat(..) => 0xA0,
+            Command::SetStartLine(_) => 0xA1,
+            Command::SetDisplayOffset(_) => 0xA2,
+            Command::AllPixelsOff => 0xA4,
+            Command::AllPixelsOn => 0xA5,
+            Command::NormalDisplayMode => 0xA6,
+            Command::InverseDisplayMode => 0xA7,
+            Command::EnablePartialDisplay(..) => 0xA8,
+            Command::ExitPartialDisplay => 0xA9,
+            Command::SetFunctionSelection(_) => 0xAB,
+            Command::DisplayOff => 0xAE,
+            Command::DisplayOn => 0xAF,
+            Command::SetPhaseLength(..) => 0xB1,
+            Command::SetDisplayClock(..) => 0xB3,
+            Command::SetDisplayEnhancementA(..) => 0xB4,
+            Command::SetGPIO(..) => 0xB5,
+            Command::SetSecondPrechargePeriod(_) => 0xB6,
+            Command::SetGrayScaleTable(_) => 0xB8,
+            Command::SetLinearGrayScaleTable => 0xB9,
+            Command::SetPrechargeVoltage(_) => 0xBB,
+            Command::SetVCOMH(_) => 0xBE,
+            Command::SetContrastCurrent(_) => 0xC1,
+            Command::SetMasterCurrent(_) => 0xC7,
+            Command::SetMuxRatio(_) => 0xCA,
+            Command::SetDisplayEnhancementB(_) => 0xD1,
+            Command::Unlock | Command::Lock => 0xFD,
+        }
+    }
+}
 
-            // Set common pins voltage level
-            Command::SetVCOMH(a) => handle_command(&[0xBE, a]),
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            // Set normal display mode
-            Command::NormalDisplayMode => handle_command(&[0xA6]),
+    /// Records the last command byte and data bytes sent.
+    #[derive(Default)]
+    struct RecordingInterface {
+        command: Option<u8>,
+        data: [u8; 16],
+        data_len: usize,
+    }
 
-            // Set all pixels off
-            Command::AllPixelsOff => handle_command(&[0xA4]),
+    impl WriteOnlyDataCommand for RecordingInterface {
+        fn send_commands(&mut self, cmds: DataFormat<'_>) -> Result<(), DisplayError> {
+            if let DataFormat::U8(cmds) = cmds {
+                self.command = Some(cmds[0]);
+            }
+            Ok(())
+        }
 
-            // Set all pixels on
-            Command::AllPixelsOn => handle_command(&[0xA5]),
+        fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+            if let DataFormat::U8(buf) = buf {
+                self.data[..buf.len()].copy_from_slice(buf);
+                self.data_len = buf.len();
+            }
+            Ok(())
+        }
+    }
 
-            // Exit partial display
-            Command::ExitPartialDisplay => handle_command(&[0xA9]),
+    /// Fails every transfer.
+    struct FailingInterface;
 
-            // Write the data following this command
-            Command::WriteRAM => handle_command(&[0x5C]),
+    impl WriteOnlyDataCommand for FailingInterface {
+        fn send_commands(&mut self, _cmds: DataFormat<'_>) -> Result<(), DisplayError> {
+            Err(DisplayError::DCError)
+        }
 
-            // Sleep mode off
-            Command::DisplayOn => handle_command(&[0xAF]),
+        fn send_data(&mut self, _buf: DataFormat<'_>) -> Result<(), DisplayError> {
+            Err(DisplayError::BusWriteError)
+        }
+    }
 
-            // Sleep mode on
-            Command::DisplayOff => handle_command(&[0xAE]),
-        };
+    fn encode(command: Command) -> (u8, RecordingInterface) {
+        let opcode = command.opcode();
+        let mut iface = RecordingInterface::default();
+        command.send(&mut iface).unwrap();
+        assert_eq!(iface.command, Some(opcode));
+        (opcode, iface)
+    }
 
-        Ok(())
+    #[test]
+    fn bus_errors_are_propagated() {
+        assert!(matches!(
+            Command::DisplayOn.send(&mut FailingInterface),
+            Err(DisplayError::DCError)
+        ));
+    }
+
+    #[test]
+    fn typed_arguments_are_encoded() {
+        let (cmd, iface) = encode(Command::SetDisplayEnhancementA(
+            Vsl::External,
+            Enhancement::Enhanced,
+        ));
+        assert_eq!(cmd, 0xB4);
+        assert_eq!(&iface.data[..iface.data_len], [0xA0, 0xFD]);
+
+        let (cmd, iface) = encode(Command::SetDisplayEnhancementB(Enhancement::Normal));
+        assert_eq!(cmd, 0xD1);
+        assert_eq!(&iface.data[..iface.data_len], [0xA2, 0x20]);
+
+        let (cmd, iface) = encode(Command::Lock);
+        assert_eq!(cmd, 0xFD);
+        assert_eq!(&iface.data[..iface.data_len], [0x16]);
+
+        let packed = [
+            (
+                Command::SetRemapFormat(RemapFormat {
+                    nibble_remap: true,
+                    com_scan_reverse: true,
+                    dual_com: true,
+                    ..RemapFormat::default()
+                }),
+                [0x14, 0x11].as_slice(),
+            ),
+            (
+                Command::SetRemapFormat(RemapFormat {
+                    vertical_increment: true,
+                    column_remap: true,
+                    com_split_odd_even: true,
+                    ..RemapFormat::default()
+                }),
+                &[0x23, 0x01],
+            ),
+            (
+                Command::SetGPIO(GpioMode::OutputHigh, GpioMode::InputEnabled),
+                &[0x07],
+            ),
+            (Command::SetFunctionSelection(Vdd::Internal), &[0x01]),
+            (Command::SetPhaseLength(2, 14), &[0xE2]),
+            (Command::SetDisplayClock(1, 9), &[0x91]),
+        ];
+        for (command, data) in packed {
+            let (_, iface) = encode(command);
+            assert_eq!(&iface.data[..iface.data_len], data, "{:?}", command);
+        }
+    }
+
+    #[test]
+    fn gray_scale_table_sends_15_entries() {
+        let mut table = [0; 15];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = i as u8 * 10;
+        }
+
+        let (cmd, iface) = encode(Command::SetGrayScaleTable(table));
+        assert_eq!(cmd, 0xB8);
+        assert_eq!(&iface.data[..iface.data_len], table);
+    }
+
+    #[test]
+    fn commands_without_data() {
+        for (command, opcode) in [
+            (Command::EnableGrayScaleTable, 0x00),
+            (Command::ReadRAM, 0x5D),
+            (Command::InverseDisplayMode, 0xA7),
+            (Command::ExitPartialDisplay, 0xA9),
+        ] {
+            let (cmd, iface) = encode(command);
+            assert_eq!(cmd, opcode);
+            assert_eq!(iface.data_len, 0);
+        }
     }
 }
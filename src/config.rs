@@ -0,0 +1,738 @@
+//! Initialization parameters
+//!
+//! [`Ssd1322Config`] holds every parameter sent by [`Ssd1322::init`](crate::display::Ssd1322::init)
+//! apart from the column/row window and multiplex ratio, which are derived from the
+//! [`DisplaySize`]. A config is created from one of the presets or through
+//! [`Ssd1322ConfigBuilder`], which checks every value against the ranges given in the SSD1322
+//! datasheet.
+use crate::command::Command;
+use crate::grayscale::GrayScaleTable;
+use crate::rotation::DisplayRotation;
+use crate::size::DisplaySize;
+
+/// Error returned when a configuration value is outside the range allowed by the datasheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The clock divider exponent must be in `0..=10`.
+    ClockDivider(u8),
+    /// The oscillator frequency must be in `0..=15`.
+    OscillatorFrequency(u8),
+    /// The display offset must be in `0..=127`.
+    DisplayOffset(u8),
+    /// The display start line must be in `0..=127`.
+    StartLine(u8),
+    /// The master current must be in `0..=15`.
+    MasterCurrent(u8),
+    /// The dimmed master current must be in `0..=15`.
+    DimMasterCurrent(u8),
+    /// The phase 1 period must be in `2..=15`.
+    Phase1Period(u8),
+    /// The phase 2 period must be in `3..=15`.
+    Phase2Period(u8),
+    /// The pre-charge voltage must be in `0..=31`.
+    PrechargeVoltage(u8),
+    /// The second pre-charge period must be in `0..=15`.
+    SecondPrechargePeriod(u8),
+    /// The VCOMH level must be in `0..=7`.
+    Vcomh(u8),
+}
+
+/// Source of the segment low voltage (VSL).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vsl {
+    /// External VSL, requires a capacitor on the VSL pin.
+    External,
+    /// Internal VSL.
+    Internal,
+}
+
+/// Display enhancement setting used by the Display Enhancement A and B commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enhancement {
+    /// Normal operation.
+    Normal,
+    /// Enhanced low gray scale display quality.
+    Enhanced,
+}
+
+/// Mode of a GPIO pin of the controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioMode {
+    /// Pin is high impedance with the input disabled.
+    InputDisabled,
+    /// Pin is high impedance with the input enabled.
+    InputEnabled,
+    /// Pin is driven low.
+    OutputLow,
+    /// Pin is driven high.
+    OutputHigh,
+}
+
+impl GpioMode {
+    pub(crate) fn bits(self) -> u8 {
+        match self {
+            GpioMode::InputDisabled => 0b00,
+            GpioMode::InputEnabled => 0b01,
+            GpioMode::OutputLow => 0b10,
+            GpioMode::OutputHigh => 0b11,
+        }
+    }
+}
+
+/// Source of the VDD supply selected by the Function Selection command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vdd {
+    /// External VDD, the internal regulator is off.
+    External,
+    /// Internal VDD regulator.
+    Internal,
+}
+
+/// Arguments of the Set Remap and Dual COM Line Mode command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RemapFormat {
+    /// Increments the row address before the column address.
+    pub vertical_increment: bool,
+    /// Maps the column addresses from the right edge of the GDDRAM.
+    pub column_remap: bool,
+    /// Swaps the two pixels of each byte.
+    pub nibble_remap: bool,
+    /// Scans the COM lines from the last one.
+    pub com_scan_reverse: bool,
+    /// Splits the COM lines into odd and even ones.
+    pub com_split_odd_even: bool,
+    /// Enables the dual COM line mode.
+    pub dual_com: bool,
+}
+
+impl RemapFormat {
+    /// The two data bytes of the command.
+    pub(crate) fn bytes(self) -> (u8, u8) {
+        let flags = [
+            (self.vertical_increment, 0),
+            (self.column_remap, 1),
+            (self.nibble_remap, 2),
+            (self.com_scan_reverse, 4),
+            (self.com_split_odd_even, 5),
+        ];
+        let a = flags
+            .iter()
+            .filter(|(set, _)| *set)
+            .fold(0, |a, (_, bit)| a | 1 << bit);
+        let b = if self.dual_com { 0x11 } else { 0x01 };
+        (a, b)
+    }
+}
+
+/// Validated initialization parameters of the SSD1322.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ssd1322Config {
+    clock_divider: u8,
+    oscillator_frequency: u8,
+    display_offset: u8,
+    start_line: u8,
+    rotation: DisplayRotation,
+    mirror_horizontal: bool,
+    mirror_vertical: bool,
+    column_remap: bool,
+    nibble_remap: bool,
+    com_scan_reverse: bool,
+    com_split_odd_even: bool,
+    dual_com: bool,
+    gpio0: GpioMode,
+    gpio1: GpioMode,
+    internal_vdd: bool,
+    vsl: Vsl,
+    gray_scale_quality: Enhancement,
+    contrast_current: u8,
+    master_current: u8,
+    dim_master_current: u8,
+    gray_scale_table: Option<GrayScaleTable>,
+    phase1_period: u8,
+    phase2_period: u8,
+    enhancement_b: Enhancement,
+    precharge_voltage: u8,
+    second_precharge_period: u8,
+    vcomh: u8,
+}
+
+impl Ssd1322Config {
+    /// Configuration for the Newhaven NHD-3.12-25664UCB2.
+    pub const fn nhd_312_25664() -> Self {
+        Self {
+            clock_divider: 1,
+            oscillator_frequency: 9,
+            display_offset: 0,
+            start_line: 0,
+            rotation: DisplayRotation::Rotate0,
+            mirror_horizontal: false,
+            mirror_vertical: false,
+            column_remap: false,
+            nibble_remap: true,
+            com_scan_reverse: true,
+            com_split_odd_even: false,
+            dual_com: true,
+            gpio0: GpioMode::InputDisabled,
+            gpio1: GpioMode::InputDisabled,
+            internal_vdd: true,
+            vsl: Vsl::External,
+            gray_scale_quality: Enhancement::Enhanced,
+            contrast_current: 0xCF,
+            master_current: 0x0F,
+            dim_master_current: 0x03,
+            gray_scale_table: None,
+            phase1_period: 2,
+            phase2_period: 14,
+            enhancement_b: Enhancement::Normal,
+            precharge_voltage: 0x1F,
+            second_precharge_period: 0x08,
+            vcomh: 0x07,
+        }
+    }
+
+    /// Configuration for the EastRising ER-OLEDM032-1.
+    pub const fn er_oledm032() -> Self {
+        let mut config = Self::nhd_312_25664();
+        config.contrast_current = 0x9F;
+        config.enhancement_b = Enhancement::Enhanced;
+        config
+    }
+
+    /// Returns a builder starting from the default configuration.
+    pub fn builder() -> Ssd1322ConfigBuilder {
+        Ssd1322ConfigBuilder::new()
+    }
+
+    /// Returns a builder starting from this configuration.
+    pub fn to_builder(self) -> Ssd1322ConfigBuilder {
+        Ssd1322ConfigBuilder { config: self }
+    }
+
+    /// Vertical display offset in rows.
+    pub fn display_offset(&self) -> u8 {
+        self.display_offset
+    }
+
+    /// Display start line.
+    pub fn start_line(&self) -> u8 {
+        self.start_line
+    }
+
+    /// Contrast current.
+    pub fn contrast_current(&self) -> u8 {
+        self.contrast_current
+    }
+
+    /// Master current.
+    pub fn master_current(&self) -> u8 {
+        self.master_current
+    }
+
+    /// Master current in [`PowerState::Dim`](crate::power::PowerState::Dim), capped at the
+    /// master current so dimming never brightens the panel.
+    pub fn dim_master_current(&self) -> u8 {
+        self.dim_master_current.min(self.master_current)
+    }
+
+    /// Rotation of the displayed image.
+    pub fn rotation(&self) -> DisplayRotation {
+        self.rotation
+    }
+
+    /// Returns the `(horizontal, vertical)` mirror flags.
+    pub fn mirror(&self) -> (bool, bool) {
+        (self.mirror_horizontal, self.mirror_vertical)
+    }
+
+    /// Custom gray scale table, `None` for the default linear table.
+    pub fn gray_scale_table(&self) -> Option<&GrayScaleTable> {
+        self.gray_scale_table.as_ref()
+    }
+
+    /// Clock divider and oscillator frequency of the Set Display Clock command.
+    pub(crate) fn display_clock(&self) -> (u8, u8) {
+        (self.clock_divider, self.oscillator_frequency)
+    }
+
+    /// Commands initializing a `SIZE` panel with this configuration.
+    pub(crate) fn init_sequence<SIZE: DisplaySize>(&self) -> impl Iterator<Item = Command> {
+        let (start_col, end_col) = SIZE::column_range(self.remaps().0);
+        let (divider, frequency) = self.display_clock();
+        let (gpio0, gpio1) = self.gpio();
+        let (phase1, phase2) = self.phase_length();
+        let [gray_scale, enable_gray_scale] = gray_scale_commands(self.gray_scale_table.as_ref());
+
+        IntoIterator::into_iter([
+            Some(Command::Unlock),
+            Some(Command::DisplayOff),
+            Some(Command::SetColumnAddress(start_col, end_col)),
+            Some(Command::SetRowAddress(0x00, (SIZE::HEIGHT - 1) as u8)),
+            Some(Command::SetDisplayClock(divider, frequency)),
+            Some(Command::SetMuxRatio(SIZE::MUX_RATIO)),
+            Some(Command::SetDisplayOffset(self.display_offset)),
+            Some(Command::SetStartLine(self.start_line)),
+            Some(Command::SetRemapFormat(self.remap_format())),
+            Some(Command::SetGPIO(gpio0, gpio1)),
+            Some(Command::SetFunctionSelection(self.function_selection())),
+            Some(Command::SetDisplayEnhancementA(
+                self.vsl,
+                self.gray_scale_quality,
+            )),
+            Some(Command::SetContrastCurrent(self.contrast_current)),
+            Some(Command::SetMasterCurrent(self.master_current)),
+            gray_scale,
+            enable_gray_scale,
+            Some(Command::SetPhaseLength(phase1, phase2)),
+            Some(Command::SetDisplayEnhancementB(self.enhancement_b)),
+            Some(Command::SetPrechargeVoltage(self.precharge_voltage)),
+            Some(Command::SetSecondPrechargePeriod(
+                self.second_precharge_period,
+            )),
+            Some(Command::SetVCOMH(self.vcomh)),
+            Some(Command::NormalDisplayMode),
+            Some(Command::DisplayOn),
+        ])
+        .flatten()
+    }
+
+    /// Returns a copy with the rotation and mirror flags replaced, which needs no validation.
+    pub(crate) fn with_orientation(
+        mut self,
+        rotation: DisplayRotation,
+        mirror_horizontal: bool,
+        mirror_vertical: bool,
+    ) -> Self {
+        self.rotation = rotation;
+        self.mirror_horizontal = mirror_horizontal;
+        self.mirror_vertical = mirror_vertical;
+        self
+    }
+
+    /// Returns a copy with the master and contrast currents replaced, which needs no validation
+    /// for a master current in `0..=15`.
+    pub(crate) fn with_currents(mut self, master_current: u8, contrast_current: u8) -> Self {
+        self.master_current = master_current;
+        self.contrast_current = contrast_current;
+        self
+    }
+
+    /// Returns the `(column, COM)` remaps after applying the rotation and mirror flags.
+    ///
+    /// The mirror flags refer to the rotated image, so they swap for 90° and 270°.
+    pub(crate) fn remaps(&self) -> (bool, bool) {
+        let (mut flip_columns, mut flip_rows) = self.rotation.flips();
+        if self.rotation.is_transposed() {
+            flip_columns ^= self.mirror_vertical;
+            flip_rows ^= self.mirror_horizontal;
+        } else {
+            flip_columns ^= self.mirror_horizontal;
+            flip_rows ^= self.mirror_vertical;
+        }
+        (
+            self.column_remap ^ flip_columns,
+            self.com_scan_reverse ^ flip_rows,
+        )
+    }
+
+    /// Arguments of the Set Remap and Dual COM Line Mode command.
+    pub(crate) fn remap_format(&self) -> RemapFormat {
+        let (column_remap, com_scan_reverse) = self.remaps();
+        RemapFormat {
+            vertical_increment: false,
+            column_remap,
+            nibble_remap: self.nibble_remap,
+            com_scan_reverse,
+            com_split_odd_even: self.com_split_odd_even,
+            dual_com: self.dual_com,
+        }
+    }
+
+    /// Modes of GPIO0 and GPIO1.
+    pub(crate) fn gpio(&self) -> (GpioMode, GpioMode) {
+        (self.gpio0, self.gpio1)
+    }
+
+    /// VDD source of the Function Selection command.
+    pub(crate) fn function_selection(&self) -> Vdd {
+        if self.internal_vdd {
+            Vdd::Internal
+        } else {
+            Vdd::External
+        }
+    }
+
+    /// Phase 1 and phase 2 periods of the Set Phase Length command.
+    pub(crate) fn phase_length(&self) -> (u8, u8) {
+        (self.phase1_period, self.phase2_period)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        fn check(
+            value: u8,
+            min: u8,
+            max: u8,
+            err: fn(u8) -> ConfigError,
+        ) -> Result<(), ConfigError> {
+            if value < min || value > max {
+                Err(err(value))
+            } else {
+                Ok(())
+            }
+        }
+
+        check(self.clock_divider, 0, 10, ConfigError::ClockDivider)?;
+        check(
+            self.oscillator_frequency,
+            0,
+            15,
+            ConfigError::OscillatorFrequency,
+        )?;
+        check(self.display_offset, 0, 127, ConfigError::DisplayOffset)?;
+        check(self.start_line, 0, 127, ConfigError::StartLine)?;
+        check(self.master_current, 0, 15, ConfigError::MasterCurrent)?;
+        check(
+            self.dim_master_current,
+            0,
+            15,
+            ConfigError::DimMasterCurrent,
+        )?;
+        check(self.phase1_period, 2, 15, ConfigError::Phase1Period)?;
+        check(self.phase2_period, 3, 15, ConfigError::Phase2Period)?;
+        check(self.precharge_voltage, 0, 31, ConfigError::PrechargeVoltage)?;
+        check(
+            self.second_precharge_period,
+            0,
+            15,
+            ConfigError::SecondPrechargePeriod,
+        )?;
+        check(self.vcomh, 0, 7, ConfigError::Vcomh)
+    }
+}
+
+/// Commands selecting `table`, or the default linear table for `None`.
+pub(crate) fn gray_scale_commands(table: Option<&GrayScaleTable>) -> [Option<Command>; 2] {
+    match table {
+        Some(table) => [
+            Some(Command::SetGrayScaleTable(*table.entries())),
+            Some(Command::EnableGrayScaleTable),
+        ],
+        None => [Some(Command::SetLinearGrayScaleTable), None],
+    }
+}
+
+impl Default for Ssd1322Config {
+    fn default() -> Self {
+        Self::nhd_312_25664()
+    }
+}
+
+/// Builder for [`Ssd1322Config`].
+///
+/// Values are checked against the datasheet ranges in [`build`](Ssd1322ConfigBuilder::build).
+#[derive(Debug, Clone, Copy)]
+pub struct Ssd1322ConfigBuilder {
+    config: Ssd1322Config,
+}
+
+impl Ssd1322ConfigBuilder {
+    /// Creates a builder starting from the default configuration.
+    pub fn new() -> Self {
+        Ssd1322Config::default().to_builder()
+    }
+
+    /// Sets the front clock divider to `2^divider`, `divider` in `0..=10`.
+    pub fn clock_divider(mut self, divider: u8) -> Self {
+        self.config.clock_divider = divider;
+        self
+    }
+
+    /// Sets the oscillator frequency, `0..=15` with higher values being faster.
+    pub fn oscillator_frequency(mut self, frequency: u8) -> Self {
+        self.config.oscillator_frequency = frequency;
+        self
+    }
+
+    /// Sets the vertical display offset in rows, `0..=127`.
+    pub fn display_offset(mut self, offset: u8) -> Self {
+        self.config.display_offset = offset;
+        self
+    }
+
+    /// Sets the GDDRAM row shown on the first display line, `0..=127`.
+    pub fn start_line(mut self, line: u8) -> Self {
+        self.config.start_line = line;
+        self
+    }
+
+    /// Sets the rotation of the displayed image.
+    pub fn rotation(mut self, rotation: DisplayRotation) -> Self {
+        self.config.rotation = rotation;
+        self
+    }
+
+    /// Mirrors the displayed image horizontally and/or vertically, after rotation.
+    pub fn mirror(mut self, horizontal: bool, vertical: bool) -> Self {
+        self.config.mirror_horizontal = horizontal;
+        self.config.mirror_vertical = vertical;
+        self
+    }
+
+    /// Reverses the column address mapping.
+    pub fn column_remap(mut self, enable: bool) -> Self {
+        self.config.column_remap = enable;
+        self
+    }
+
+    /// Swaps the nibbles of each data byte.
+    pub fn nibble_remap(mut self, enable: bool) -> Self {
+        self.config.nibble_remap = enable;
+        self
+    }
+
+    /// Scans the COM lines from COM[N-1] to COM0.
+    pub fn com_scan_reverse(mut self, enable: bool) -> Self {
+        self.config.com_scan_reverse = enable;
+        self
+    }
+
+    /// Splits the COM lines into odd and even lines.
+    pub fn com_split_odd_even(mut self, enable: bool) -> Self {
+        self.config.com_split_odd_even = enable;
+        self
+    }
+
+    /// Enables the dual COM line mode.
+    pub fn dual_com(mut self, enable: bool) -> Self {
+        self.config.dual_com = enable;
+        self
+    }
+
+    /// Sets the mode of the GPIO0 and GPIO1 pins.
+    pub fn gpio(mut self, gpio0: GpioMode, gpio1: GpioMode) -> Self {
+        self.config.gpio0 = gpio0;
+        self.config.gpio1 = gpio1;
+        self
+    }
+
+    /// Selects the internal VDD regulator instead of an external VDD supply.
+    pub fn internal_vdd(mut self, enable: bool) -> Self {
+        self.config.internal_vdd = enable;
+        self
+    }
+
+    /// Selects the VSL source.
+    pub fn vsl(mut self, vsl: Vsl) -> Self {
+        self.config.vsl = vsl;
+        self
+    }
+
+    /// Sets the low gray scale display quality (Display Enhancement A).
+    pub fn gray_scale_quality(mut self, quality: Enhancement) -> Self {
+        self.config.gray_scale_quality = quality;
+        self
+    }
+
+    /// Sets the contrast current, `0..=255`.
+    pub fn contrast_current(mut self, contrast: u8) -> Self {
+        self.config.contrast_current = contrast;
+        self
+    }
+
+    /// Sets the master current, `0..=15`.
+    pub fn master_current(mut self, current: u8) -> Self {
+        self.config.master_current = current;
+        self
+    }
+
+    /// Sets the master current used while dimmed, `0..=15`. The master current of the
+    /// brightness is used instead when it is lower.
+    pub fn dim_master_current(mut self, current: u8) -> Self {
+        self.config.dim_master_current = current;
+        self
+    }
+
+    /// Uses a custom gray scale table instead of the default linear table.
+    pub fn gray_scale_table(mut self, table: GrayScaleTable) -> Self {
+        self.config.gray_scale_table = Some(table);
+        self
+    }
+
+    /// Sets the reset (phase 1, `2..=15`) and first pre-charge (phase 2, `3..=15`) periods.
+    pub fn phase_length(mut self, phase1: u8, phase2: u8) -> Self {
+        self.config.phase1_period = phase1;
+        self.config.phase2_period = phase2;
+        self
+    }
+
+    /// Sets Display Enhancement B.
+    pub fn enhancement_b(mut self, enhancement: Enhancement) -> Self {
+        self.config.enhancement_b = enhancement;
+        self
+    }
+
+    /// Sets the pre-charge voltage level, `0..=31`.
+    pub fn precharge_voltage(mut self, voltage: u8) -> Self {
+        self.config.precharge_voltage = voltage;
+        self
+    }
+
+    /// Sets the second pre-charge period in DCLKs, `0..=15`.
+    pub fn second_precharge_period(mut self, period: u8) -> Self {
+        self.config.second_precharge_period = period;
+        self
+    }
+
+    /// Sets the COM deselect voltage level, `0..=7`.
+    pub fn vcomh(mut self, level: u8) -> Self {
+        self.config.vcomh = level;
+        self
+    }
+
+    /// Validates the parameters and returns the config.
+    pub fn build(self) -> Result<Ssd1322Config, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+impl Default for Ssd1322ConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::size::DisplaySize256x64;
+
+    #[test]
+    fn nhd_preset_matches_init_sequence() {
+        let config = Ssd1322Config::nhd_312_25664();
+        assert_eq!(config.display_clock(), (1, 9));
+        assert_eq!(config.remap_format().bytes(), (0x14, 0x11));
+        assert_eq!(
+            config.gpio(),
+            (GpioMode::InputDisabled, GpioMode::InputDisabled)
+        );
+        assert_eq!(config.function_selection(), Vdd::Internal);
+        assert_eq!(config.phase_length(), (2, 14));
+        assert_eq!(config.contrast_current(), 0xCF);
+        assert_eq!(config.master_current(), 0x0F);
+        assert_eq!(Ssd1322Config::builder().build(), Ok(config));
+    }
+
+    #[test]
+    fn init_sequence_matches_nhd_312() {
+        let opcodes = [
+            0xFD, 0xAE, 0x15, 0x75, 0xB3, 0xCA, 0xA2, 0xA1, 0xA0, 0xB5, 0xAB, 0xB4, 0xC1, 0xC7,
+            0xB9, 0xB1, 0xD1, 0xBB, 0xB6, 0xBE, 0xA6, 0xAF,
+        ];
+        let config = Ssd1322Config::nhd_312_25664();
+        let sequence = config.init_sequence::<DisplaySize256x64>();
+        assert!(sequence.map(|command| command.opcode()).eq(opcodes));
+
+        let config = config
+            .to_builder()
+            .gray_scale_table(GrayScaleTable::GAMMA_2_2)
+            .build()
+            .unwrap();
+        let mut sequence = config.init_sequence::<DisplaySize256x64>().skip(14);
+        assert!(matches!(
+            sequence.next(),
+            Some(Command::SetGrayScaleTable(_))
+        ));
+        assert!(matches!(
+            sequence.next(),
+            Some(Command::EnableGrayScaleTable)
+        ));
+        assert_eq!(sequence.next(), Some(Command::SetPhaseLength(2, 14)));
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        let builder = Ssd1322Config::builder();
+        assert_eq!(
+            builder.clock_divider(11).build(),
+            Err(ConfigError::ClockDivider(11))
+        );
+        assert_eq!(
+            builder.phase_length(1, 5).build(),
+            Err(ConfigError::Phase1Period(1))
+        );
+        assert_eq!(
+            builder.phase_length(5, 2).build(),
+            Err(ConfigError::Phase2Period(2))
+        );
+        assert_eq!(builder.vcomh(8).build(), Err(ConfigError::Vcomh(8)));
+        assert_eq!(
+            builder.start_line(128).build(),
+            Err(ConfigError::StartLine(128))
+        );
+        assert_eq!(
+            builder.dim_master_current(16).build(),
+            Err(ConfigError::DimMasterCurrent(16))
+        );
+    }
+
+    #[test]
+    fn rotation_and_mirror_set_remaps() {
+        let remap = |rotation, horizontal, vertical| {
+            Ssd1322Config::builder()
+                .rotation(rotation)
+                .mirror(horizontal, vertical)
+                .build()
+                .unwrap()
+                .remap_format()
+                .bytes()
+        };
+
+        assert_eq!(remap(DisplayRotation::Rotate0, false, false), (0x14, 0x11));
+        assert_eq!(
+            remap(DisplayRotation::Rotate180, false, false),
+            (0x06, 0x11)
+        );
+        assert_eq!(remap(DisplayRotation::Rotate0, true, false), (0x16, 0x11));
+        assert_eq!(remap(DisplayRotation::Rotate0, false, true), (0x04, 0x11));
+        assert_eq!(remap(DisplayRotation::Rotate180, true, true), (0x14, 0x11));
+        assert_eq!(remap(DisplayRotation::Rotate90, false, false), (0x16, 0x11));
+        assert_eq!(
+            remap(DisplayRotation::Rotate270, false, false),
+            (0x04, 0x11)
+        );
+        assert_eq!(remap(DisplayRotation::Rotate90, true, false), (0x06, 0x11));
+    }
+
+    #[test]
+    fn builder_encodes_fields() {
+        let config = Ssd1322Config::er_oledm032()
+            .to_builder()
+            .clock_divider(0)
+            .oscillator_frequency(0xC)
+            .nibble_remap(false)
+            .com_scan_reverse(false)
+            .dual_com(false)
+            .gpio(GpioMode::OutputHigh, GpioMode::InputEnabled)
+            .internal_vdd(false)
+            .build()
+            .unwrap();
+        assert_eq!(config.display_clock(), (0, 0xC));
+        assert_eq!(config.remap_format().bytes(), (0x00, 0x01));
+        assert_eq!(
+            config.gpio(),
+            (GpioMode::OutputHigh, GpioMode::InputEnabled)
+        );
+        assert_eq!(config.function_selection(), Vdd::External);
+        assert_eq!(config.contrast_current(), 0x9F);
+    }
+
+    #[test]
+    fn dim_master_current_is_capped() {
+        let builder = Ssd1322Config::default().to_builder().dim_master_current(8);
+        let config = builder.master_current(5).build().unwrap();
+        assert_eq!(config.dim_master_current(), 5);
+        let config = builder.master_current(12).build().unwrap();
+        assert_eq!(config.dim_master_current(), 8);
+        assert_eq!(config.with_currents(2, 0x80).dim_master_current(), 2);
+    }
+}
@@ -0,0 +1,504 @@
+//! Direct drawing without a framebuffer
+//!
+//! [`DirectSsd1322`] streams drawing operations straight into the GDDRAM instead of keeping a
+//! `WIDTH * HEIGHT / 2` byte framebuffer, for MCUs without that much RAM to spare. A column
+//! address of the SSD1322 covers 4 pixels, so areas whose left edge and width are multiples of 4
+//! pixels after clipping are written as a single window. Other areas need the pixels sharing
+//! their edge column addresses: with an interface implementing [`ReadData`], such as a parallel
+//! bus, they are read back with Read RAM and merged, otherwise [`Error::Unaligned`] is returned.
+use crate::command::Command;
+use crate::config::Ssd1322Config;
+use crate::display::start_write;
+use crate::error::{Error, FlushStage};
+use crate::size::{DisplaySize, DisplaySize256x64, Geometry, GDDRAM_COLUMNS, PIXELS_PER_COLUMN};
+use crate::window::{Window, BYTES_PER_COLUMN};
+use core::marker::PhantomData;
+use display_interface::{DataFormat::U8, DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::{
+    draw_target::DrawTarget, geometry::OriginDimensions, pixelcolor::Gray4, prelude::*,
+    primitives::Rectangle, Pixel,
+};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+
+/// Largest number of bytes in a GDDRAM row.
+const MAX_ROW_BYTES: usize = GDDRAM_COLUMNS as usize * BYTES_PER_COLUMN;
+
+/// Display interface that can read data back from the controller, which the SSD1322 supports on
+/// its 6800 and 8080 parallel buses but not over SPI.
+///
+/// `display-interface` has no read support, so a parallel bus implements this next to
+/// [`WriteOnlyDataCommand`]. The driver sends Read RAM as a command first, `read_data` then
+/// reads the data bytes with D/C high, e.g. latching each byte while RD# is low on an 8080 bus:
+///
+/// ```
+/// # use display_interface::{DataFormat, WriteOnlyDataCommand};
+/// use display_interface::DisplayError;
+/// use embedded_hal::digital::OutputPin;
+/// use ssd1322_di::{direct::{DirectSsd1322, ReadData}, size::DisplaySize256x64};
+///
+/// /// The 8 data pins of the bus, switched to inputs for reading.
+/// pub trait DataPins {
+///     fn write(&mut self, byte: u8);
+///     fn read(&mut self) -> u8;
+/// }
+///
+/// /// 8080 bus with CS# tied low.
+/// pub struct Bus8080<P, DC, WR, RD> {
+///     pins: P,
+///     dc: DC,
+///     wr: WR,
+///     rd: RD,
+/// }
+///
+/// impl<P: DataPins, DC: OutputPin, WR: OutputPin, RD: OutputPin> ReadData
+///     for Bus8080<P, DC, WR, RD>
+/// {
+///     fn read_data(&mut self, buf: &mut [u8]) -> Result<(), DisplayError> {
+///         self.dc.set_high().map_err(|_| DisplayError::DCError)?;
+///         for byte in buf {
+///             self.rd.set_low().map_err(|_| DisplayError::BusWriteError)?;
+///             *byte = self.pins.read();
+///             self.rd.set_high().map_err(|_| DisplayError::BusWriteError)?;
+///         }
+///         Ok(())
+///     }
+/// }
+/// # impl<P: DataPins, DC: OutputPin, WR: OutputPin, RD: OutputPin> WriteOnlyDataCommand
+/// #     for Bus8080<P, DC, WR, RD>
+/// # {
+/// #     fn send_commands(&mut self, _: DataFormat<'_>) -> Result<(), DisplayError> { Ok(()) }
+/// #     fn send_data(&mut self, _: DataFormat<'_>) -> Result<(), DisplayError> { Ok(()) }
+/// # }
+///
+/// fn display<P: DataPins, DC: OutputPin, WR: OutputPin, RD: OutputPin>(
+///     bus: Bus8080<P, DC, WR, RD>,
+/// ) -> DirectSsd1322<Bus8080<P, DC, WR, RD>> {
+///     DirectSsd1322::with_readback(bus, DisplaySize256x64)
+/// }
+/// ```
+pub trait ReadData {
+    /// Reads `buf.len()` data bytes.
+    fn read_data(&mut self, buf: &mut [u8]) -> Result<(), DisplayError>;
+}
+
+/// Reads data bytes from the interface `DI`.
+type ReadFn<DI> = fn(&mut DI, &mut [u8]) -> Result<(), DisplayError>;
+
+/// SSD1322 display drawn without a framebuffer.
+pub struct DirectSsd1322<DI, SIZE: DisplaySize = DisplaySize256x64> {
+    display: DI,
+    /// Set when the interface implements [`ReadData`].
+    read: Option<ReadFn<DI>>,
+    config: Ssd1322Config,
+    size: PhantomData<SIZE>,
+}
+
+impl<DI: WriteOnlyDataCommand> DirectSsd1322<DI> {
+    /// Creates the SSD1322 Display for a 256x64 panel.
+    ///
+    /// The device needs to be reset before use.
+    pub fn new(display: DI) -> Self {
+        Self::with_size(display, DisplaySize256x64)
+    }
+}
+
+impl<DI: WriteOnlyDataCommand, SIZE: DisplaySize> DirectSsd1322<DI, SIZE> {
+    /// Creates the SSD1322 Display for the panel geometry `SIZE`.
+    ///
+    /// The device needs to be reset before use.
+    pub fn with_size(display: DI, _size: SIZE) -> Self {
+        let () = Geometry::<SIZE>::CHECK;
+        Self {
+            display,
+            read: None,
+            config: Ssd1322Config::default(),
+            size: PhantomData,
+        }
+    }
+
+    /// Creates the SSD1322 Display for the panel geometry `SIZE` on an interface that can be
+    /// read, so areas and pixels not covering whole column addresses can be drawn.
+    ///
+    /// The device needs to be reset before use.
+    pub fn with_readback(display: DI, size: SIZE) -> Self
+    where
+        DI: ReadData,
+    {
+        Self {
+            read: Some(DI::read_data),
+            ..Self::with_size(display, size)
+        }
+    }
+
+    /// Resets the display.
+    pub fn reset<RST, DELAY>(&mut self, rst: &mut RST, delay: &mut DELAY) -> Result<(), Error>
+    where
+        RST: OutputPin,
+        DELAY: DelayNs,
+    {
+        rst.set_low().map_err(|_| Error::Reset)?;
+        delay.delay_ms(10);
+
+        rst.set_high().map_err(|_| Error::Reset)?;
+        delay.delay_ms(200);
+
+        Ok(())
+    }
+
+    /// Initializes the display with the current configuration, which is
+    /// [`Ssd1322Config::default`] unless set by [`init_with_config`](Self::init_with_config).
+    ///
+    /// The GDDRAM is not cleared, call [`clear`](DrawTarget::clear) before drawing.
+    pub fn init(&mut self) -> Result<(), Error> {
+        self.init_with_config(self.config)
+    }
+
+    /// Initializes the display with `config` and keeps it as the current configuration.
+    pub fn init_with_config(&mut self, config: Ssd1322Config) -> Result<(), Error> {
+        self.config = config;
+        for command in config.init_sequence::<SIZE>() {
+            self.send_command(command)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the configuration used by [`init`](Self::init).
+    pub fn config(&self) -> &Ssd1322Config {
+        &self.config
+    }
+
+    /// Consumes the display driver and returns the underlying display interface.
+    pub fn release(self) -> DI {
+        self.display
+    }
+
+    /// Returns the underlying display interface, e.g. to inspect a mock interface in tests.
+    pub fn interface(&self) -> &DI {
+        &self.display
+    }
+
+    /// Allows to send custom commands to the display.
+    pub fn send_command(&mut self, command: Command) -> Result<(), Error> {
+        let opcode = command.opcode();
+        command
+            .send(&mut self.display)
+            .map_err(|source| Error::Command { opcode, source })
+    }
+
+    /// Pixel columns and rows of the part of `area` inside the panel, in framebuffer
+    /// orientation, or `None` if nothing is left after clipping.
+    fn clip(&self, area: &Rectangle) -> Option<Rectangle> {
+        let area = if self.config.rotation().is_transposed() {
+            Rectangle::new(
+                Point::new(area.top_left.y, area.top_left.x),
+                Size::new(area.size.height, area.size.width),
+            )
+        } else {
+            *area
+        };
+        let panel = Rectangle::new(
+            Point::zero(),
+            Size::new(SIZE::WIDTH.into(), SIZE::HEIGHT.into()),
+        );
+        let area = area.intersection(&panel);
+        area.bottom_right().map(|_| area)
+    }
+
+    /// Writes the pixels of `area`, which is in framebuffer orientation and inside the panel.
+    ///
+    /// For each row, `fill` sets the nibbles of the pixels in the bytes of the column addresses
+    /// covering them, the first nibble being the left edge of `area` rounded down to a column
+    /// address, and returns the number of pixels set from the left edge on. Drawing stops after a
+    /// row with fewer pixels. Partially covered column addresses are read back and merged, or
+    /// return [`Error::Unaligned`] when the interface can't be read.
+    fn write_area<F>(&mut self, area: &Rectangle, mut fill: F) -> Result<(), Error>
+    where
+        F: FnMut(&mut [u8]) -> usize,
+    {
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+        let pixels_per_column = usize::from(PIXELS_PER_COLUMN);
+        let (x0, x1) = (area.top_left.x as usize, bottom_right.x as usize);
+        let rows = (area.top_left.y as u8, bottom_right.y as u8);
+        let aligned = x0 % pixels_per_column == 0 && (x1 + 1) % pixels_per_column == 0;
+        if !aligned && self.read.is_none() {
+            return Err(Error::Unaligned);
+        }
+
+        let first_byte = Window::from_pixels((x0, x0), rows).first_byte;
+        // Whole column addresses are streamed in a window reaching the bottom of the area, opened
+        // with the first bytes to send. Reads move the RAM address though.
+        let mut streaming = false;
+
+        let mut bytes = [0; MAX_ROW_BYTES];
+        for row in rows.0..=rows.1 {
+            let drawn = fill(&mut bytes);
+            if drawn == 0 {
+                break;
+            }
+            let last = x0 + drawn - 1;
+            let mut window = Window::from_pixels((x0, last), (row, row));
+
+            let partial_right = last % pixels_per_column != pixels_per_column - 1;
+            match self.read {
+                Some(read) if !aligned || partial_right => {
+                    let right = first_byte + window.num_bytes - BYTES_PER_COLUMN;
+                    if x0 % pixels_per_column != 0 {
+                        let column = &mut bytes[..BYTES_PER_COLUMN];
+                        self.merge_column(read, row, first_byte, column, (x0, last))?;
+                    }
+                    if partial_right && (right != first_byte || x0 % pixels_per_column == 0) {
+                        let column = &mut bytes[right - first_byte..window.num_bytes];
+                        self.merge_column(read, row, right, column, (x0, last))?;
+                    }
+                    self.start_write(&window)?;
+                    streaming = false;
+                }
+                _ => {
+                    if partial_right {
+                        // Colors ran out inside a column address, which can't be completed.
+                        window.num_bytes =
+                            (last + 1) / pixels_per_column * BYTES_PER_COLUMN - first_byte;
+                        if window.num_bytes == 0 {
+                            break;
+                        }
+                    }
+                    if !streaming {
+                        self.start_write(&Window::from_pixels((x0, x1), (row, rows.1)))?;
+                        streaming = true;
+                    }
+                }
+            }
+            self.send_row(row, &bytes[..window.num_bytes])?;
+            if last < x1 {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the column address holding the bytes `byte..byte + 2` of framebuffer row `row` and
+    /// copies its pixels outside `x0..=x1` into `bytes`.
+    fn merge_column(
+        &mut self,
+        read: ReadFn<DI>,
+        row: u8,
+        byte: usize,
+        bytes: &mut [u8],
+        (x0, x1): (usize, usize),
+    ) -> Result<(), Error> {
+        let column_range = SIZE::column_range(self.config.remaps().0);
+        let (column, _) = Window::from_bytes((byte, byte), (row, row)).columns(column_range);
+        self.send_command(Command::SetColumnAddress(column, column))?;
+        self.send_command(Command::SetRowAddress(row, row))?;
+        self.send_command(Command::ReadRAM)?;
+
+        // The first byte read after Read RAM is a dummy byte.
+        let mut data = [0; 3];
+        read(&mut self.display, &mut data).map_err(|source| Error::Command {
+            opcode: Command::ReadRAM.opcode(),
+            source,
+        })?;
+        for i in 0..usize::from(PIXELS_PER_COLUMN) {
+            let x = byte * 2 + i;
+            if x < x0 || x > x1 {
+                set_nibble(bytes, i, nibble(&data[1..], i));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the address window to `window` and starts writing to the GDDRAM.
+    fn start_write(&mut self, window: &Window) -> Result<(), Error> {
+        let column_range = SIZE::column_range(self.config.remaps().0);
+        start_write(&mut self.display, window, column_range, window.rows.0)
+    }
+
+    /// Sends the GDDRAM bytes of framebuffer row `row`.
+    fn send_row(&mut self, row: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.display
+            .send_data(U8(bytes))
+            .map_err(|source| Error::Flush {
+                stage: FlushStage::Data,
+                row,
+                source,
+            })
+    }
+}
+
+/// Gray level of pixel `index` in the packed `bytes`.
+fn nibble(bytes: &[u8], index: usize) -> u8 {
+    let byte = bytes[index / 2];
+    if index % 2 == 1 {
+        byte & 0x0F
+    } else {
+        byte >> 4
+    }
+}
+
+/// Sets pixel `index` in the packed `bytes` to `luma`.
+fn set_nibble(bytes: &mut [u8], index: usize, luma: u8) {
+    let byte = &mut bytes[index / 2];
+    *byte = if index % 2 == 1 {
+        (*byte & 0xF0) | luma
+    } else {
+        (*byte & 0x0F) | (luma << 4)
+    };
+}
+
+impl<DI: WriteOnlyDataCommand, SIZE: DisplaySize> DrawTarget for DirectSsd1322<DI, SIZE> {
+    type Color = Gray4;
+    type Error = Error;
+
+    /// Each pixel is read back and merged into its column address, which needs an interface
+    /// that can be read. Without one, any pixel inside the panel returns [`Error::Unaligned`].
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let bounds = self.bounding_box();
+        for Pixel(point, color) in pixels {
+            if bounds.contains(point) {
+                self.fill_solid(&Rectangle::new(point, Size::new(1, 1)), color)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        if self.config.rotation().is_transposed() {
+            // Logical rows are GDDRAM columns, so bytes can't be packed.
+            let pixels = area.points().zip(colors).map(|(p, c)| Pixel(p, c));
+            return self.draw_iter(pixels);
+        }
+        let Some(clipped) = self.clip(area) else {
+            return Ok(());
+        };
+
+        let width = area.size.width as usize;
+        let skip_left = (clipped.top_left.x - area.top_left.x) as usize;
+        let rows_above = (clipped.top_left.y - area.top_left.y) as usize;
+        let offset = clipped.top_left.x as usize % usize::from(PIXELS_PER_COLUMN);
+        let mut colors = colors.into_iter();
+        if rows_above > 0 && colors.nth(rows_above * width - 1).is_none() {
+            return Ok(());
+        }
+        self.write_area(&clipped, |bytes| {
+            let mut row_colors = colors.by_ref().take(width);
+            if skip_left > 0 && row_colors.nth(skip_left - 1).is_none() {
+                return 0;
+            }
+            let mut drawn = 0;
+            for color in row_colors.by_ref().take(clipped.size.width as usize) {
+                set_nibble(bytes, offset + drawn, color.luma());
+                drawn += 1;
+            }
+            // Skip the colors clipped at the right
+            row_colors.for_each(drop);
+            drawn
+        })
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let Some(clipped) = self.clip(area) else {
+            return Ok(());
+        };
+
+        let width = clipped.size.width as usize;
+        let offset = clipped.top_left.x as usize % usize::from(PIXELS_PER_COLUMN);
+        let luma = color.luma();
+        self.write_area(&clipped, |bytes| {
+            for i in offset..offset + width {
+                set_nibble(bytes, i, luma);
+            }
+            width
+        })
+    }
+}
+
+impl<DI, SIZE: DisplaySize> OriginDimensions for DirectSsd1322<DI, SIZE> {
+    fn size(&self) -> Size {
+        if self.config.rotation().is_transposed() {
+            Size::new(SIZE::HEIGHT.into(), SIZE::WIDTH.into())
+        } else {
+            Size::new(SIZE::WIDTH.into(), SIZE::HEIGHT.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use display_interface::{DataFormat, DisplayError};
+
+    /// Counts the commands and data bytes sent.
+    #[derive(Default)]
+    struct DataCounter {
+        commands: usize,
+        data_bytes: usize,
+    }
+
+    impl WriteOnlyDataCommand for DataCounter {
+        fn send_commands(&mut self, _cmds: DataFormat<'_>) -> Result<(), DisplayError> {
+            self.commands += 1;
+            Ok(())
+        }
+
+        fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+            if let U8(slice) = buf {
+                self.data_bytes += slice.len();
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn only_whole_columns_are_drawn() {
+        let mut disp = DirectSsd1322::new(DataCounter::default());
+        let aligned = Rectangle::new(Point::new(4, 1), Size::new(8, 2));
+        disp.fill_solid(&aligned, Gray4::WHITE).unwrap();
+        // Column and row addresses, then 4 bytes per row
+        assert_eq!(disp.display.data_bytes, 4 + 2 * 4);
+
+        for area in [
+            Rectangle::new(Point::new(2, 0), Size::new(8, 2)),
+            Rectangle::new(Point::new(4, 0), Size::new(6, 2)),
+        ] {
+            assert!(matches!(
+                disp.fill_solid(&area, Gray4::WHITE),
+                Err(Error::Unaligned)
+            ));
+        }
+        assert!(matches!(
+            Pixel(Point::new(3, 3), Gray4::WHITE).draw(&mut disp),
+            Err(Error::Unaligned)
+        ));
+
+        // Clipping at the panel edges keeps the area aligned
+        let clipped = Rectangle::new(Point::new(-4, 62), Size::new(8, 8));
+        disp.fill_solid(&clipped, Gray4::WHITE).unwrap();
+        assert_eq!(disp.display.data_bytes, 12 + 4 + 2 * 2);
+    }
+
+    #[test]
+    fn colors_ending_in_the_first_column_send_nothing() {
+        let mut disp = DirectSsd1322::new(DataCounter::default());
+        let area = Rectangle::new(Point::new(4, 1), Size::new(8, 2));
+        disp.fill_contiguous(&area, [Gray4::WHITE; 3]).unwrap();
+        disp.fill_contiguous(&area, []).unwrap();
+        assert_eq!((disp.display.commands, disp.display.data_bytes), (0, 0));
+
+        // Colors ending inside the second row send the first row only
+        disp.fill_contiguous(&area, [Gray4::WHITE; 10]).unwrap();
+        assert_eq!((disp.display.commands, disp.display.data_bytes), (3, 4 + 4));
+    }
+}
@@ -0,0 +1,381 @@
+//! Change tracking strategies
+//!
+//! The framebuffer reports every changed byte to a [`DirtyTracker`], which decides which
+//! regions are sent by the next flush. The tracker is selected with the last type parameter of
+//! the driver, e.g. `Ssd1322<DI, DisplaySize256x64, DirtyRects<4>>`. [`DirtyRects`] suits a
+//! few compact changes, [`DirtyRows`] many narrow changes spread over the rows.
+
+/// Approximate cost, in bytes, of setting up one write window (column address, row address and
+/// write RAM commands).
+pub const WINDOW_OVERHEAD: usize = 8;
+
+/// Region of the framebuffer, in bytes for the columns and in pixels for the rows. Both ranges
+/// are inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirtyRegion {
+    /// First and last byte column.
+    pub columns: (u8, u8),
+    /// First and last row.
+    pub rows: (u8, u8),
+}
+
+impl DirtyRegion {
+    /// Region covering a single byte.
+    pub fn point(column: u8, row: u8) -> Self {
+        Self {
+            columns: (column, column),
+            rows: (row, row),
+        }
+    }
+
+    /// Number of bytes covered by the region.
+    pub fn area(&self) -> usize {
+        let width = usize::from(self.columns.1 - self.columns.0) + 1;
+        let height = usize::from(self.rows.1 - self.rows.0) + 1;
+        width * height
+    }
+
+    /// Returns true if the byte at `column`, `row` is inside the region.
+    pub fn contains(&self, column: u8, row: u8) -> bool {
+        (self.columns.0..=self.columns.1).contains(&column)
+            && (self.rows.0..=self.rows.1).contains(&row)
+    }
+
+    /// Smallest region covering both regions.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            columns: (
+                self.columns.0.min(other.columns.0),
+                self.columns.1.max(other.columns.1),
+            ),
+            rows: (self.rows.0.min(other.rows.0), self.rows.1.max(other.rows.1)),
+        }
+    }
+}
+
+/// Records the changed parts of the framebuffer.
+pub trait DirtyTracker: Default {
+    /// Number of rows the tracker can record, a panel with more rows fails to build.
+    const MAX_ROWS: usize = usize::MAX;
+
+    /// Records a change of the byte at `column`, `row`.
+    fn mark(&mut self, column: u8, row: u8);
+
+    /// Records a change of every byte in `region`.
+    fn mark_region(&mut self, region: DirtyRegion);
+
+    /// Returns the regions changed since the last [`reset`](DirtyTracker::reset).
+    fn regions(&self) -> impl Iterator<Item = DirtyRegion> + '_;
+
+    /// Forgets all changes.
+    fn reset(&mut self);
+}
+
+/// Tracks up to `N` rectangles.
+///
+/// A change close to an existing rectangle grows it, other changes start a new rectangle until
+/// `N` are in use, after which the rectangle that grows the least absorbs the change.
+/// Rectangles that overlap or are cheaper to send together are coalesced. With `N = 1` this is
+/// a single bounding box.
+///
+/// `N` must be at least 1, a tracker without room for a rectangle doesn't compile:
+///
+/// ```compile_fail
+/// # use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+/// # struct Bus;
+/// # impl WriteOnlyDataCommand for Bus {
+/// #     fn send_commands(&mut self, _: DataFormat<'_>) -> Result<(), DisplayError> { Ok(()) }
+/// #     fn send_data(&mut self, _: DataFormat<'_>) -> Result<(), DisplayError> { Ok(()) }
+/// # }
+/// use ssd1322_di::{dirty::DirtyRects, display::Ssd1322, size::DisplaySize256x64};
+///
+/// let disp = Ssd1322::<_, DisplaySize256x64, DirtyRects<0>>::with_tracker(Bus);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DirtyRects<const N: usize> {
+    rects: [DirtyRegion; N],
+    len: usize,
+}
+
+impl<const N: usize> Default for DirtyRects<N> {
+    fn default() -> Self {
+        let () = Self::HAS_ROOM;
+        Self {
+            rects: [DirtyRegion::point(0, 0); N],
+            len: 0,
+        }
+    }
+}
+
+impl<const N: usize> DirtyRects<N> {
+    /// Fails the build when the tracker has no room for a rectangle.
+    const HAS_ROOM: () = assert!(N > 0, "DirtyRects needs room for at least one rectangle");
+
+    /// Adds `region`, growing or coalescing the existing rectangles as needed.
+    fn add(&mut self, region: DirtyRegion) {
+        if self.rects[..self.len]
+            .iter()
+            .any(|rect| rect.union(&region) == *rect)
+        {
+            return;
+        }
+
+        // Rectangle that grows the least by absorbing the region
+        let best = self.rects[..self.len]
+            .iter()
+            .enumerate()
+            .map(|(i, rect)| (i, rect.union(&region).area() - rect.area()))
+            .min_by_key(|&(_, growth)| growth);
+
+        match best {
+            Some((i, growth)) if growth <= region.area() + WINDOW_OVERHEAD || self.len == N => {
+                self.rects[i] = self.rects[i].union(&region);
+                self.coalesce(i);
+            }
+            _ => {
+                self.rects[self.len] = region;
+                self.len += 1;
+                self.coalesce(self.len - 1);
+            }
+        }
+    }
+
+    /// Merges the rectangle at `index` with every rectangle that is cheaper to send together.
+    fn coalesce(&mut self, mut index: usize) {
+        let mut i = 0;
+        while i < self.len {
+            if i != index {
+                let merged = self.rects[index].union(&self.rects[i]);
+                if merged.area()
+                    <= self.rects[index].area() + self.rects[i].area() + WINDOW_OVERHEAD
+                {
+                    self.rects[index] = merged;
+                    self.len -= 1;
+                    self.rects[i] = self.rects[self.len];
+                    if index == self.len {
+                        index = i;
+                    }
+                    // The grown rectangle may now absorb rectangles that were checked already
+                    i = 0;
+                    continue;
+                }
+            }
+            i += 1;
+        }
+    }
+}
+
+impl<const N: usize> DirtyTracker for DirtyRects<N> {
+    fn mark(&mut self, column: u8, row: u8) {
+        self.add(DirtyRegion::point(column, row));
+    }
+
+    fn mark_region(&mut self, region: DirtyRegion) {
+        self.add(region);
+    }
+
+    fn regions(&self) -> impl Iterator<Item = DirtyRegion> + '_ {
+        self.rects[..self.len].iter().copied()
+    }
+
+    fn reset(&mut self) {
+        self.len = 0;
+    }
+}
+
+/// Tracks one span of changed bytes per row, for panels with up to `ROWS` rows.
+///
+/// Each row is sent as its own window, except that consecutive rows with the same span share
+/// one. This suits changes that touch many rows narrowly, like a scrolling list of text.
+///
+/// A driver pairing it with a taller panel doesn't compile:
+///
+/// ```compile_fail
+/// # use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+/// # struct Bus;
+/// # impl WriteOnlyDataCommand for Bus {
+/// #     fn send_commands(&mut self, _: DataFormat<'_>) -> Result<(), DisplayError> { Ok(()) }
+/// #     fn send_data(&mut self, _: DataFormat<'_>) -> Result<(), DisplayError> { Ok(()) }
+/// # }
+/// use ssd1322_di::{dirty::DirtyRows, display::Ssd1322, size::DisplaySize480x128};
+///
+/// let disp = Ssd1322::<_, DisplaySize480x128, DirtyRows<64>>::with_tracker(Bus);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DirtyRows<const ROWS: usize = 128> {
+    spans: [Option<(u8, u8)>; ROWS],
+}
+
+impl<const ROWS: usize> Default for DirtyRows<ROWS> {
+    fn default() -> Self {
+        Self {
+            spans: [None; ROWS],
+        }
+    }
+}
+
+impl<const ROWS: usize> DirtyRows<ROWS> {
+    /// Widens the span of `row` to cover `columns`.
+    fn add(&mut self, columns: (u8, u8), row: u8) {
+        if let Some(span) = self.spans.get_mut(usize::from(row)) {
+            *span = Some(match *span {
+                Some((first, last)) => (first.min(columns.0), last.max(columns.1)),
+                None => columns,
+            });
+        }
+    }
+}
+
+impl<const ROWS: usize> DirtyTracker for DirtyRows<ROWS> {
+    const MAX_ROWS: usize = ROWS;
+
+    fn mark(&mut self, column: u8, row: u8) {
+        self.add((column, column), row);
+    }
+
+    fn mark_region(&mut self, region: DirtyRegion) {
+        for row in region.rows.0..=region.rows.1 {
+            self.add(region.columns, row);
+        }
+    }
+
+    fn regions(&self) -> impl Iterator<Item = DirtyRegion> + '_ {
+        let mut row = 0;
+        core::iter::from_fn(move || {
+            // Skip the unchanged rows
+            while row < ROWS && self.spans[row].is_none() {
+                row += 1;
+            }
+            let columns = (*self.spans.get(row)?)?;
+            let first = row;
+            while row < ROWS && self.spans[row] == Some(columns) {
+                row += 1;
+            }
+
+            Some(DirtyRegion {
+                columns,
+                rows: (first as u8, (row - 1) as u8),
+            })
+        })
+    }
+
+    fn reset(&mut self) {
+        self.spans = [None; ROWS];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_rect_is_a_bounding_box() {
+        let mut dirty = DirtyRects::<1>::default();
+        dirty.mark(0, 0);
+        dirty.mark(100, 50);
+        dirty.mark(10, 3);
+
+        let mut regions = dirty.regions();
+        assert_eq!(
+            regions.next(),
+            Some(DirtyRegion {
+                columns: (0, 100),
+                rows: (0, 50)
+            })
+        );
+        assert_eq!(regions.next(), None);
+    }
+
+    #[test]
+    fn distant_changes_use_separate_rects() {
+        let mut dirty = DirtyRects::<4>::default();
+        // Clock in the top left, status icon in the bottom right
+        for row in 0..8 {
+            for column in 0..4 {
+                dirty.mark(column, row);
+                dirty.mark(124 + column, 56 + row);
+            }
+        }
+
+        assert!(dirty.regions().eq([
+            DirtyRegion {
+                columns: (0, 3),
+                rows: (0, 7)
+            },
+            DirtyRegion {
+                columns: (124, 127),
+                rows: (56, 63)
+            }
+        ]));
+    }
+
+    #[test]
+    fn overlapping_rects_are_coalesced() {
+        let mut dirty = DirtyRects::<4>::default();
+        dirty.mark_region(DirtyRegion {
+            columns: (0, 9),
+            rows: (0, 9),
+        });
+        dirty.mark_region(DirtyRegion {
+            columns: (50, 59),
+            rows: (0, 9),
+        });
+        dirty.mark_region(DirtyRegion {
+            columns: (5, 54),
+            rows: (0, 9),
+        });
+
+        assert!(dirty.regions().eq([DirtyRegion {
+            columns: (0, 59),
+            rows: (0, 9)
+        }]));
+    }
+
+    #[test]
+    fn full_tracker_grows_cheapest_rect() {
+        let mut dirty = DirtyRects::<2>::default();
+        dirty.mark(0, 0);
+        dirty.mark(100, 0);
+        dirty.mark(100, 60);
+
+        assert_eq!(dirty.regions().count(), 2);
+        assert!(dirty.regions().any(|region| region
+            == DirtyRegion {
+                columns: (100, 100),
+                rows: (0, 60)
+            }));
+
+        dirty.reset();
+        assert_eq!(dirty.regions().count(), 0);
+    }
+
+    #[test]
+    fn rows_with_same_span_share_a_region() {
+        let mut dirty = DirtyRows::<64>::default();
+        for row in 0..4 {
+            dirty.mark(2, row);
+            dirty.mark(5, row);
+        }
+        dirty.mark(10, 4);
+        dirty.mark(0, 9);
+        dirty.mark(70, 100);
+
+        assert!(dirty.regions().eq([
+            DirtyRegion {
+                columns: (2, 5),
+                rows: (0, 3)
+            },
+            DirtyRegion {
+                columns: (10, 10),
+                rows: (4, 4)
+            },
+            DirtyRegion {
+                columns: (0, 0),
+                rows: (9, 9)
+            }
+        ]));
+
+        dirty.reset();
+        assert_eq!(dirty.regions().count(), 0);
+    }
+}
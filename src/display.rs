@@ -1,173 +1,773 @@
 //! main display module
-use core::convert::TryInto;
-
+use crate::brightness;
+use crate::burnin::{BurnIn, BurnInProtection, OnTime};
 use crate::command::Command;
-use display_interface::{DataFormat::U8, DisplayError, WriteOnlyDataCommand};
+use crate::config::{gray_scale_commands, Ssd1322Config, Vdd};
+use crate::dirty::{DirtyRects, DirtyTracker};
+use crate::error::{Error, FlushStage};
+use crate::framebuffer::FrameBuffer;
+use crate::grayscale::GrayScaleTable;
+use crate::mode::DisplayMode;
+use crate::power::PowerState;
+use crate::rotation::DisplayRotation;
+use crate::size::{DisplaySize, DisplaySize256x64, GDDRAM_COLUMNS, GDDRAM_ROWS};
+#[cfg(feature = "std")]
+use crate::snapshot::Snapshot;
+use crate::window::{Window, BYTES_PER_COLUMN};
+use core::ops::RangeInclusive;
+use display_interface::{DataFormat::U8, WriteOnlyDataCommand};
 use embedded_graphics::{
-    draw_target::DrawTarget, geometry::OriginDimensions, pixelcolor::Gray4, prelude::*, Pixel,
+    draw_target::DrawTarget, geometry::OriginDimensions, pixelcolor::Gray4, prelude::*,
+    primitives::Rectangle, Pixel,
 };
-use embedded_hal::blocking::delay::DelayMs;
-use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+
+pub use crate::framebuffer::BoundingBox;
 
-const DISPLAY_WIDTH: usize = 256;
-const DISPLAY_HEIGHT: usize = 64;
-const BUFFER_SIZE: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT / 2;
+/// Black GDDRAM row, written into the rows outside the framebuffer.
+const BLANK_ROW: [u8; GDDRAM_COLUMNS as usize * BYTES_PER_COLUMN] =
+    [0; GDDRAM_COLUMNS as usize * BYTES_PER_COLUMN];
 
 /// Represents the SSD1322 Display.
 ///
-/// Use this struct to initialize the driver.
-pub struct Ssd1322<DI> {
+/// Use this struct to initialize the driver. The panel geometry is described by `SIZE`, which
+/// defaults to the 256x64 panel of the NHD-3.12-25664UCB2. The changes to send on
+/// [`flush`](Self::flush) are recorded by the [`DirtyTracker`] `D`, a single bounding box by
+/// default. The framebuffer is stored in `B`, an array inside the struct unless storage is
+/// supplied to [`with_buffer`](Self::with_buffer).
+pub struct Ssd1322<
+    DI,
+    SIZE: DisplaySize = DisplaySize256x64,
+    D = DirtyRects<1>,
+    B = <SIZE as DisplaySize>::Buffer,
+> {
     display: DI,
-    buffer: [u8; BUFFER_SIZE],
-    bounding_box: Option<([u8; 2], [u8; 2])>,
-    num_changed: u16,
-}
-
-/// Provides an optimized way to capture changes to the framebuffer.
-pub trait BoundingBox {
-    /// Updates the bounding_box field to the modified area. The bounding_box unit is in bytes.
-    fn update_box(&mut self, x: u8, y: u8);
+    framebuffer: FrameBuffer<SIZE, D, B>,
+    config: Ssd1322Config,
+    power_state: PowerState,
+    display_mode: DisplayMode,
+    burn_in: Option<BurnIn>,
+    /// Buffer drawn into in double-buffered mode, `framebuffer` then holding the presented frame.
+    back: Option<FrameBuffer<SIZE, D, &'static mut [u8]>>,
 }
 
 impl<DI: WriteOnlyDataCommand> Ssd1322<DI> {
-    /// Creates the SSD1322 Display.
+    /// Creates the SSD1322 Display for a 256x64 panel.
     ///
     /// The device needs to be reset before use.
     pub fn new(display: DI) -> Self {
+        Self::with_size(display, DisplaySize256x64)
+    }
+}
+
+impl<DI: WriteOnlyDataCommand, SIZE: DisplaySize> Ssd1322<DI, SIZE> {
+    /// Creates the SSD1322 Display for the panel geometry `SIZE`.
+    ///
+    /// The device needs to be reset before use.
+    pub fn with_size(display: DI, _size: SIZE) -> Self {
+        Self::with_tracker(display)
+    }
+}
+
+impl<DI: WriteOnlyDataCommand, SIZE: DisplaySize, D: DirtyTracker> Ssd1322<DI, SIZE, D> {
+    /// Creates the SSD1322 Display with the panel geometry and dirty tracker given by the type
+    /// parameters, e.g. `Ssd1322::<_, DisplaySize256x64, DirtyRects<4>>::with_tracker(di)`.
+    ///
+    /// The device needs to be reset before use.
+    pub fn with_tracker(display: DI) -> Self {
+        Self::from_framebuffer(display, FrameBuffer::new())
+    }
+}
+
+impl<DI: WriteOnlyDataCommand, SIZE: DisplaySize, B: AsMut<[u8]> + AsRef<[u8]>>
+    Ssd1322<DI, SIZE, DirtyRects<1>, B>
+{
+    /// Creates the SSD1322 Display for the panel geometry `SIZE` with the framebuffer stored in
+    /// `buffer`, e.g. a `&'static mut [u8]` placed in a specific RAM section.
+    ///
+    /// `buffer` must hold `WIDTH * HEIGHT / 2` bytes and is cleared. The device needs to be reset
+    /// before use.
+    pub fn with_buffer(display: DI, _size: SIZE, buffer: B) -> Result<Self, Error> {
+        Self::with_tracker_and_buffer(display, buffer)
+    }
+}
+
+impl<
+        DI: WriteOnlyDataCommand,
+        SIZE: DisplaySize,
+        D: DirtyTracker,
+        B: AsMut<[u8]> + AsRef<[u8]>,
+    > Ssd1322<DI, SIZE, D, B>
+{
+    /// Creates the SSD1322 Display with the panel geometry and dirty tracker given by the type
+    /// parameters and the framebuffer stored in `buffer`, see [`with_buffer`](Self::with_buffer).
+    pub fn with_tracker_and_buffer(display: DI, mut buffer: B) -> Result<Self, Error> {
+        if buffer.as_ref().len() != FrameBuffer::<SIZE, D>::BYTES {
+            return Err(Error::BufferSize);
+        }
+        buffer.as_mut().fill(0);
+
+        Ok(Self::from_framebuffer(
+            display,
+            FrameBuffer::with_buffer(buffer),
+        ))
+    }
+
+    /// Creates the driver around `framebuffer`.
+    fn from_framebuffer(display: DI, framebuffer: FrameBuffer<SIZE, D, B>) -> Self {
         Self {
             display,
-            buffer: [0; BUFFER_SIZE],
-            bounding_box: None,
-            num_changed: 0,
+            framebuffer,
+            config: Ssd1322Config::default(),
+            power_state: PowerState::Sleep,
+            display_mode: DisplayMode::Normal,
+            burn_in: None,
+            back: None,
         }
     }
 
     /// Resets the display.
-    pub fn reset<RST, DELAY>(
-        &mut self,
-        rst: &mut RST,
-        delay: &mut DELAY,
-    ) -> Result<(), DisplayError>
+    pub fn reset<RST, DELAY>(&mut self, rst: &mut RST, delay: &mut DELAY) -> Result<(), Error>
     where
         RST: OutputPin,
-        DELAY: DelayMs<u8>,
+        DELAY: DelayNs,
     {
-        rst.set_low().map_err(|_| DisplayError::BusWriteError)?;
+        rst.set_low().map_err(|_| Error::Reset)?;
         delay.delay_ms(10);
 
-        rst.set_high().map_err(|_| DisplayError::BusWriteError)?;
+        rst.set_high().map_err(|_| Error::Reset)?;
         delay.delay_ms(200);
 
         Ok(())
     }
 
-    /// Initializes the display.
-    pub fn init(&mut self) -> Result<(), DisplayError> {
-        self.send_command(Command::Unlock)?;
-        self.send_command(Command::DisplayOff)?;
-        self.send_command(Command::SetColumnAddress(0x1C, 0x5B))?;
-        self.send_command(Command::SetRowAddress(0x00, 0x3F))?;
-        self.send_command(Command::SetDisplayClock(0x91))?;
-        self.send_command(Command::SetMuxRatio(0x3F))?;
-        self.send_command(Command::SetDisplayOffset(0x00))?;
-        self.send_command(Command::SetStartLine(0x00))?;
-        self.send_command(Command::SetRemapFormat(0x14, 0x11))?;
-        self.send_command(Command::SetGPIO(0x00))?;
-        self.send_command(Command::SetFunctionSelection(0x01))?;
-        self.send_command(Command::SetDisplayEnhancementA(0xA0, 0xFD))?;
-        self.send_command(Command::SetContrastCurrent(0xCF))?;
-        self.send_command(Command::SetMasterCurrent(0x0F))?;
-        self.send_command(Command::SetLinearGrayScaleTable)?;
-        self.send_command(Command::SetPhaseLength(0xE2))?;
-        self.send_command(Command::SetDisplayEnhancementB(0xA2, 0x20))?;
-        self.send_command(Command::SetPrechargeVoltage(0x1F))?;
-        self.send_command(Command::SetPrechargePeriod(0x08))?;
-        self.send_command(Command::SetVCOMH(0x07))?;
-        self.send_command(Command::NormalDisplayMode)?;
-        //self.send_command(Command::AllPixelsOn)?;
-        self.send_command(Command::DisplayOn)?;
+    /// Resets the display using embedded-hal 0.2 pin and delay implementations.
+    #[cfg(feature = "embedded-hal-02")]
+    pub fn reset_eh02<RST, DELAY>(&mut self, rst: &mut RST, delay: &mut DELAY) -> Result<(), Error>
+    where
+        RST: embedded_hal_02::digital::v2::OutputPin,
+        DELAY: embedded_hal_02::blocking::delay::DelayMs<u8>,
+    {
+        rst.set_low().map_err(|_| Error::Reset)?;
+        delay.delay_ms(10);
+
+        rst.set_high().map_err(|_| Error::Reset)?;
+        delay.delay_ms(200);
 
         Ok(())
     }
 
+    /// Initializes the display with the current configuration, which is
+    /// [`Ssd1322Config::default`] unless set by [`init_with_config`](Self::init_with_config).
+    pub fn init(&mut self) -> Result<(), Error> {
+        self.init_with_config(self.config)
+    }
+
+    /// Initializes the display with `config` and keeps it as the current configuration.
+    pub fn init_with_config(&mut self, config: Ssd1322Config) -> Result<(), Error> {
+        self.config = config;
+        self.framebuffer.reset_scroll();
+        if let Some(back) = &mut self.back {
+            // The recorded changes refer to the frame before the init, compare everything
+            back.reset_scroll();
+            back.mark_all_dirty();
+        }
+        // The init sequence leaves the partial display mode
+        self.framebuffer
+            .set_visible_rows((0, (SIZE::HEIGHT - 1) as u8));
+        for command in config.init_sequence::<SIZE>() {
+            self.send_command(command)?;
+        }
+        self.power_state = PowerState::On;
+        self.display_mode = DisplayMode::Normal;
+        if let Some(burn_in) = &mut self.burn_in {
+            burn_in.restart();
+        }
+
+        Ok(())
+    }
+
+    /// Returns the configuration used by [`init`](Self::init).
+    pub fn config(&self) -> &Ssd1322Config {
+        &self.config
+    }
+
     /// Consumes the display driver and returns the underlying display interface.
     pub fn release(self) -> DI {
         self.display
     }
 
-    /// Allows to send custom commands to the display.
-    pub fn send_command(&mut self, command: Command) -> Result<(), DisplayError> {
-        command.send(&mut self.display)
+    /// Returns the underlying display interface, e.g. to inspect a mock interface in tests.
+    pub fn interface(&self) -> &DI {
+        &self.display
     }
 
-    /// Flushes the entire display, and makes the output visible on the screen.
-    pub fn flush_all(&mut self) -> Result<(), DisplayError> {
-        self.send_command(Command::SetColumnAddress(0x1C, 0x5B))?;
-        self.send_command(Command::SetRowAddress(0x00, 0x3F))?;
-        self.send_command(Command::WriteRAM)?;
-        self.display.send_data(U8(&self.buffer))
-    }
-
-    /// Flushes only the changed portion of the display.
-    pub fn flush(&mut self) -> Result<(), DisplayError> {
-        if let Some((mut col_addr, row_addr)) = self.bounding_box {
-            col_addr[0] -= col_addr[0] % 2;
-            col_addr[1] -= col_addr[1] % 2;
-            let num_col_bytes: usize = (col_addr[1] - col_addr[0] + 2).into();
-
-            // Convert bytes to column address
-            self.send_command(Command::SetColumnAddress(
-                col_addr[0] / 2 + 0x1C,
-                col_addr[1] / 2 + 0x1C,
+    /// Returns the underlying display interface mutably.
+    pub fn interface_mut(&mut self) -> &mut DI {
+        &mut self.display
+    }
+
+    /// Uploads a custom gray scale table and enables it.
+    pub fn set_gray_scale_table(&mut self, table: &GrayScaleTable) -> Result<(), Error> {
+        for command in IntoIterator::into_iter(gray_scale_commands(Some(table))).flatten() {
+            self.send_command(command)?;
+        }
+
+        Ok(())
+    }
+
+    /// Selects the default linear gray scale table of the controller.
+    pub fn set_linear_gray_scale_table(&mut self) -> Result<(), Error> {
+        self.send_command(Command::SetLinearGrayScaleTable)
+    }
+
+    /// Rotates the displayed image.
+    ///
+    /// The whole framebuffer is marked as changed, and the application is expected to redraw it
+    /// for a change between the landscape and portrait orientations.
+    pub fn set_rotation(&mut self, rotation: DisplayRotation) -> Result<(), Error> {
+        let (horizontal, vertical) = self.config.mirror();
+        self.config = self.config.with_orientation(rotation, horizontal, vertical);
+        self.apply_remap()
+    }
+
+    /// Mirrors the displayed image horizontally and/or vertically, after rotation.
+    pub fn set_mirror(&mut self, horizontal: bool, vertical: bool) -> Result<(), Error> {
+        let rotation = self.config.rotation();
+        self.config = self.config.with_orientation(rotation, horizontal, vertical);
+        self.apply_remap()
+    }
+
+    /// Sends the remap of the current configuration and marks the whole display as changed.
+    fn apply_remap(&mut self) -> Result<(), Error> {
+        self.send_command(Command::SetRemapFormat(self.config.remap_format()))?;
+        self.framebuffer.mark_all_dirty();
+
+        Ok(())
+    }
+
+    /// Changes the power state, skipping the commands if the display is already in `state`.
+    ///
+    /// Leaving [`PowerState::Off`] turns the VDD regulator back on and rewrites the GDDRAM from
+    /// the framebuffer before the display turns on.
+    pub fn set_power_state(&mut self, state: PowerState) -> Result<(), Error> {
+        self.change_power_state(state, false)
+    }
+
+    /// Turns the display on, rewriting the GDDRAM from the framebuffer first if `restore` is set
+    /// or the display was [`PowerState::Off`].
+    pub fn wake(&mut self, restore: bool) -> Result<(), Error> {
+        self.change_power_state(PowerState::On, restore)
+    }
+
+    /// Turns the display off, keeping the GDDRAM content.
+    pub fn sleep(&mut self) -> Result<(), Error> {
+        self.set_power_state(PowerState::Sleep)
+    }
+
+    /// Current power state.
+    pub fn power_state(&self) -> PowerState {
+        self.power_state
+    }
+
+    fn change_power_state(&mut self, state: PowerState, mut restore: bool) -> Result<(), Error> {
+        let from = self.power_state;
+        if from == state && !restore {
+            return Ok(());
+        }
+
+        if from.is_display_on() && !state.is_display_on() {
+            self.send_command(Command::DisplayOff)?;
+        }
+        if state == PowerState::Off {
+            if from != PowerState::Off {
+                // Selecting the external VDD turns the internal regulator off
+                self.send_command(Command::SetFunctionSelection(Vdd::External))?;
+            }
+        } else if from == PowerState::Off {
+            self.send_command(Command::SetFunctionSelection(
+                self.config.function_selection(),
             ))?;
-            self.send_command(Command::SetRowAddress(row_addr[0], row_addr[1]))?;
-            self.send_command(Command::WriteRAM)?;
+            restore = true;
+        }
+        if restore {
+            self.flush_all()?;
+            self.framebuffer.reset_dirty();
+        }
+        if state.is_display_on() {
+            if state != from {
+                let current = if state == PowerState::Dim {
+                    self.config.dim_master_current()
+                } else {
+                    self.config.master_current()
+                };
+                self.send_command(Command::SetMasterCurrent(current))?;
+            }
+            if !from.is_display_on() {
+                self.send_command(Command::DisplayOn)?;
+            }
+        }
+        self.power_state = state;
+
+        Ok(())
+    }
+
+    /// Selects how the display shows the GDDRAM, skipping the command if `mode` is already
+    /// selected. The framebuffer is left untouched.
+    pub fn set_display_mode(&mut self, mode: DisplayMode) -> Result<(), Error> {
+        if mode != self.display_mode {
+            self.send_command(mode.command())?;
+            self.display_mode = mode;
+        }
+
+        Ok(())
+    }
+
+    /// Current display mode.
+    pub fn display_mode(&self) -> DisplayMode {
+        self.display_mode
+    }
+
+    /// Enables the burn-in protection with `settings`, restarting its timers and on-time.
+    pub fn enable_burn_in_protection(&mut self, settings: BurnInProtection) {
+        self.burn_in = Some(BurnIn::new(settings));
+    }
+
+    /// Disables the burn-in protection, moving the image back and stopping the screen saver.
+    pub fn disable_burn_in_protection(&mut self) -> Result<(), Error> {
+        if let Some(burn_in) = self.burn_in.take() {
+            self.send_command(Command::SetDisplayOffset(self.config.display_offset()))?;
+            if let Some(state) = burn_in.resume {
+                self.set_power_state(state)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reports that `elapsed_ms` have passed since the last tick, to be called periodically
+    /// while the burn-in protection is enabled.
+    ///
+    /// Adds the on-time of each region, shifts the image when the shift interval has passed and
+    /// starts the screen saver after the idle timeout.
+    pub fn tick(&mut self, elapsed_ms: u32) -> Result<(), Error> {
+        let mut burn_in = match self.burn_in.take() {
+            Some(burn_in) => burn_in,
+            None => return Ok(()),
+        };
+        let result = self.tick_burn_in(&mut burn_in, elapsed_ms);
+        self.burn_in = Some(burn_in);
+        result
+    }
+
+    fn tick_burn_in(&mut self, burn_in: &mut BurnIn, elapsed_ms: u32) -> Result<(), Error> {
+        if self.power_state.is_display_on() {
+            burn_in.add_on_time(
+                self.framebuffer.buffer.as_ref(),
+                FrameBuffer::<SIZE, D>::ROW_BYTES,
+                self.display_mode,
+                elapsed_ms,
+            );
+        }
+
+        if let Some(shift) = burn_in.next_shift(elapsed_ms) {
+            if !burn_in.margin_blank {
+                self.blank_shift_margin(burn_in.settings.max_shift)?;
+                burn_in.margin_blank = true;
+            }
+            let offset = (self.config.display_offset() + shift) % GDDRAM_ROWS;
+            self.send_command(Command::SetDisplayOffset(offset))?;
+        }
+
+        burn_in.idle_ms = burn_in.idle_ms.saturating_add(elapsed_ms);
+        if let Some(timeout) = burn_in.settings.idle_timeout_ms {
+            if burn_in.resume.is_none() && burn_in.idle_ms >= timeout {
+                let resume = self.power_state;
+                self.set_power_state(burn_in.settings.screen_saver)?;
+                burn_in.resume = Some(resume);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes black into the GDDRAM rows above the framebuffer that a shift of up to `max_shift`
+    /// rows moves into view, which would otherwise show power-on garbage or rows scrolled out.
+    /// Nothing is written when the framebuffer leaves no rows of the GDDRAM free.
+    fn blank_shift_margin(&mut self, max_shift: u8) -> Result<(), Error> {
+        let rows = max_shift.min(GDDRAM_ROWS - SIZE::HEIGHT as u8);
+        if rows == 0 {
+            return Ok(());
+        }
 
-            for i in row_addr[0]..=row_addr[1] {
-                let start_col_byte: usize = col_addr[0] as usize + (i as usize * DISPLAY_WIDTH / 2);
-                let end_col_byte: usize = start_col_byte + num_col_bytes;
+        let margin = Window {
+            first_byte: 0,
+            num_bytes: FrameBuffer::<SIZE, D>::ROW_BYTES,
+            rows: (0, rows - 1),
+        };
+        let scroll = (self.framebuffer.scroll + (GDDRAM_ROWS - rows)) % GDDRAM_ROWS;
+        let column_range = self.column_range();
+        for (segment, gddram_row) in margin.segments(scroll) {
+            for (command, _) in write_sequence(&segment, column_range, gddram_row) {
+                self.send_command(command)?;
+            }
+            for _ in segment.rows.0..=segment.rows.1 {
                 self.display
-                    .send_data(U8(&self.buffer[start_col_byte..end_col_byte]))?;
+                    .send_data(U8(&BLANK_ROW[..segment.num_bytes]))
+                    .map_err(|source| Error::Command {
+                        opcode: Command::WriteRAM.opcode(),
+                        source,
+                    })?;
             }
+        }
+
+        Ok(())
+    }
 
-            // Reset the bounding_box
-            self.bounding_box = None;
-            self.num_changed = 0;
+    /// Reports user activity, restarting the idle timer and stopping the screen saver.
+    pub fn report_activity(&mut self) -> Result<(), Error> {
+        let resume = match &mut self.burn_in {
+            Some(burn_in) => {
+                burn_in.idle_ms = 0;
+                burn_in.resume.take()
+            }
+            None => None,
+        };
+        if let Some(state) = resume {
+            self.set_power_state(state)?;
         }
 
         Ok(())
     }
-}
 
-impl<DI> BoundingBox for Ssd1322<DI> {
-    fn update_box(&mut self, x: u8, y: u8) {
-        match self.bounding_box {
-            Some((col_addr, row_addr)) => {
-                let mut new_col_addr: [u8; 2] = col_addr;
-                let mut new_row_addr: [u8; 2] = row_addr;
-
-                // Column address update
-                if x / 2 < col_addr[0] {
-                    new_col_addr = [x / 2, col_addr[1]];
-                } else if x / 2 > col_addr[1] {
-                    new_col_addr = [col_addr[0], x / 2];
+    /// Lit time of each panel region while the burn-in protection is enabled, e.g. to find
+    /// regions that need to be moved or dimmed.
+    pub fn on_time(&self) -> Option<&OnTime> {
+        self.burn_in.as_ref().map(|burn_in| &burn_in.on_time)
+    }
+
+    /// Sets the perceptual brightness `level`, `0..=255`, through the master and contrast
+    /// currents given by [`brightness::currents`].
+    ///
+    /// The currents replace the ones of the configuration, so they are kept across
+    /// [`PowerState`] changes. While dimmed only the contrast current changes right away.
+    pub fn set_brightness(&mut self, level: u8) -> Result<(), Error> {
+        let (master, contrast) = brightness::currents(level);
+        let dimmed = self.config.dim_master_current();
+        self.config = self.config.with_currents(master, contrast);
+        self.send_command(Command::SetContrastCurrent(contrast))?;
+        if self.power_state != PowerState::Dim {
+            self.send_command(Command::SetMasterCurrent(master))?;
+        } else if self.config.dim_master_current() != dimmed {
+            self.send_command(Command::SetMasterCurrent(self.config.dim_master_current()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Perceptual brightness level of the current master and contrast currents.
+    pub fn brightness(&self) -> u8 {
+        brightness::level(self.config.master_current(), self.config.contrast_current())
+    }
+
+    /// Fades from the current brightness to `level` over about `duration_ms`, one level at a
+    /// time.
+    pub fn fade_brightness<DELAY: DelayNs>(
+        &mut self,
+        level: u8,
+        duration_ms: u32,
+        delay: &mut DELAY,
+    ) -> Result<(), Error> {
+        let from = self.brightness();
+        let steps = from.abs_diff(level);
+        if steps == 0 {
+            return self.set_brightness(level);
+        }
+
+        let step_us = duration_ms.saturating_mul(1000) / u32::from(steps);
+        for step in 1..=steps {
+            let current = if level > from {
+                from + step
+            } else {
+                from - step
+            };
+            // Neighbouring levels can share the same currents
+            let currents = brightness::currents(current);
+            if currents != (self.config.master_current(), self.config.contrast_current()) {
+                self.set_brightness(current)?;
+            }
+            delay.delay_us(step_us);
+        }
+
+        Ok(())
+    }
+
+    /// Allows to send custom commands to the display.
+    pub fn send_command(&mut self, command: Command) -> Result<(), Error> {
+        let opcode = command.opcode();
+        command
+            .send(&mut self.display)
+            .map_err(|source| Error::Command { opcode, source })
+    }
+
+    /// Flushes the entire display, and makes the output visible on the screen.
+    pub fn flush_all(&mut self) -> Result<(), Error> {
+        let window = FrameBuffer::<SIZE, D>::full_window();
+        let column_range = self.column_range();
+        for (segment, gddram_row) in self.framebuffer.segments(window) {
+            start_write(&mut self.display, &segment, column_range, gddram_row)?;
+            self.display
+                .send_data(U8(self.framebuffer.rows_bytes(segment.rows)))
+                .map_err(|source| Error::Flush {
+                    stage: FlushStage::Data,
+                    row: segment.rows.0,
+                    source,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes only the changed portions of the display.
+    ///
+    /// Each region recorded by the dirty tracker is sent as its own window, unless sending the
+    /// whole framebuffer is cheaper.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let column_range = self.column_range();
+        for window in self.framebuffer.dirty_windows() {
+            for (segment, gddram_row) in self.framebuffer.segments(window) {
+                start_write(&mut self.display, &segment, column_range, gddram_row)?;
+
+                for i in segment.rows.0..=segment.rows.1 {
+                    self.display
+                        .send_data(U8(self.framebuffer.row_bytes(&segment, i)))
+                        .map_err(|source| Error::Flush {
+                            stage: FlushStage::Data,
+                            row: i,
+                            source,
+                        })?;
                 }
+            }
+        }
+
+        self.framebuffer.reset_dirty();
+
+        Ok(())
+    }
 
-                // Row address update
-                if y < row_addr[0] {
-                    new_row_addr = [y, row_addr[1]];
-                } else if y > row_addr[1] {
-                    new_row_addr = [row_addr[0], y];
+    /// Draws into the separate back buffer `back` from now on, so that half-drawn frames are
+    /// never flushed. [`present`](Self::present) makes the drawn frame visible.
+    ///
+    /// `back` must hold `WIDTH * HEIGHT / 2` bytes and is initialized with the current frame.
+    pub fn enable_double_buffering(&mut self, back: &'static mut [u8]) -> Result<(), Error> {
+        if back.len() != FrameBuffer::<SIZE, D>::BYTES {
+            return Err(Error::BufferSize);
+        }
+        self.disable_double_buffering();
+
+        back.copy_from_slice(self.framebuffer.buffer.as_ref());
+        self.back = Some(FrameBuffer::with_buffer(back));
+
+        Ok(())
+    }
+
+    /// Draws into the framebuffer again, returning the back buffer. Changes not presented yet
+    /// are kept and sent on the next [`flush`](Self::flush).
+    pub fn disable_double_buffering(&mut self) -> Option<&'static mut [u8]> {
+        let mut back = self.back.take()?;
+        self.framebuffer.copy_changes(&mut back);
+        Some(back.buffer)
+    }
+
+    /// Returns true if drawing goes to a back buffer.
+    pub fn is_double_buffered(&self) -> bool {
+        self.back.is_some()
+    }
+
+    /// Makes the frame drawn into the back buffer visible: the bytes that differ from the
+    /// presented frame are copied and flushed. Without double buffering this is a
+    /// [`flush`](Self::flush).
+    ///
+    /// [`flush`](Self::flush) alone only sends presented changes, e.g. when called by another
+    /// task while a frame is being drawn.
+    pub fn present(&mut self) -> Result<(), Error> {
+        if let Some(back) = &mut self.back {
+            self.framebuffer.copy_changes(back);
+        }
+        self.flush()
+    }
+
+    /// Scrolls the display to `line`, the vertical position inside the 128 row GDDRAM, wrapping
+    /// at 128.
+    ///
+    /// Pending changes are flushed first, then the display start line moves the rows already in
+    /// the GDDRAM. The rows scrolled into view hold the rows scrolled out at the other edge until
+    /// redrawn, and are sent on the next [`flush`](Self::flush). With the burn-in protection
+    /// enabled, the rows its shift moves into view are written black again.
+    pub fn scroll_to(&mut self, line: u8) -> Result<(), Error> {
+        self.flush()?;
+
+        let line = line % GDDRAM_ROWS;
+        let start_line = (self.config.start_line() + line) % GDDRAM_ROWS;
+        self.send_command(Command::SetStartLine(start_line))?;
+        let lines = line.wrapping_sub(self.framebuffer.scroll) % GDDRAM_ROWS;
+        self.framebuffer.scroll_by(lines);
+        if let Some(back) = &mut self.back {
+            // Rotated like the presented frame, the recorded changes no longer match their rows
+            back.scroll_by(lines);
+            back.mark_all_dirty();
+        }
+        if let Some(burn_in) = &self.burn_in {
+            // The rows above the framebuffer now hold rows scrolled out
+            self.blank_shift_margin(burn_in.settings.max_shift)?;
+        }
+
+        Ok(())
+    }
+
+    /// Scrolls the content up by `lines`, or down for a negative value.
+    pub fn scroll_by(&mut self, lines: i8) -> Result<(), Error> {
+        self.scroll_to(self.framebuffer.scroll.wrapping_add(lines as u8) % GDDRAM_ROWS)
+    }
+
+    /// Scrolls by `lines` one line at a time, flushing and waiting `step_ms` after each line.
+    pub fn smooth_scroll<DELAY: DelayNs>(
+        &mut self,
+        lines: i8,
+        step_ms: u32,
+        delay: &mut DELAY,
+    ) -> Result<(), Error> {
+        for _ in 0..lines.unsigned_abs() {
+            self.scroll_by(lines.signum())?;
+            self.flush()?;
+            delay.delay_ms(step_ms);
+        }
+
+        Ok(())
+    }
+
+    /// Current scroll position set by [`scroll_to`](Self::scroll_to).
+    pub fn scroll_position(&self) -> u8 {
+        self.framebuffer.scroll
+    }
+
+    /// Drives only the framebuffer rows `rows`, turning the other rows off to cut the panel
+    /// current, e.g. to show a status bar while idle.
+    ///
+    /// Until [`exit_partial_display`](Self::exit_partial_display), flushes only send the changes
+    /// inside these rows.
+    pub fn enter_partial_display(&mut self, rows: RangeInclusive<u8>) -> Result<(), Error> {
+        let (first, last) = rows.into_inner();
+        if first > last || u16::from(last) >= SIZE::HEIGHT {
+            return Err(Error::InvalidRows);
+        }
+
+        self.send_command(Command::EnablePartialDisplay(first, last))?;
+        self.framebuffer.set_visible_rows((first, last));
+
+        Ok(())
+    }
+
+    /// Drives all rows again. The rows that were off are sent on the next
+    /// [`flush`](Self::flush).
+    pub fn exit_partial_display(&mut self) -> Result<(), Error> {
+        self.send_command(Command::ExitPartialDisplay)?;
+        self.framebuffer
+            .set_visible_rows((0, (SIZE::HEIGHT - 1) as u8));
+
+        Ok(())
+    }
+
+    /// Rows driven in partial display mode, or `None` if all rows are driven.
+    pub fn partial_display(&self) -> Option<RangeInclusive<u8>> {
+        let (first, last) = self.framebuffer.visible_rows;
+        let all = first == 0 && u16::from(last) + 1 == SIZE::HEIGHT;
+        (!all).then_some(first..=last)
+    }
+}
+
+/// Sets the column and row address window and starts writing to the GDDRAM at row
+/// `gddram_row`.
+pub(crate) fn start_write<DI: WriteOnlyDataCommand>(
+    display: &mut DI,
+    window: &Window,
+    column_range: (u8, u8),
+    gddram_row: u8,
+) -> Result<(), Error> {
+    for (command, stage) in write_sequence(window, column_range, gddram_row) {
+        command.send(display).map_err(|source| Error::Flush {
+            stage,
+            row: window.rows.0,
+            source,
+        })?;
+    }
+
+    Ok(())
+}
+
+impl<DI, SIZE: DisplaySize, D, B> Ssd1322<DI, SIZE, D, B> {
+    /// Column address range of the panel for the current remap.
+    fn column_range(&self) -> (u8, u8) {
+        SIZE::column_range(self.config.remaps().0)
+    }
+}
+
+/// Commands selecting `window` of a panel at the column addresses `column_range`, with its first
+/// row at GDDRAM row `gddram_row`, and starting a GDDRAM write.
+pub(crate) fn write_sequence(
+    window: &Window,
+    column_range: (u8, u8),
+    gddram_row: u8,
+) -> [(Command, FlushStage); 3] {
+    let (start_col, end_col) = window.columns(column_range);
+    let end_row = gddram_row + (window.rows.1 - window.rows.0);
+    [
+        (
+            Command::SetColumnAddress(start_col, end_col),
+            FlushStage::ColumnAddress,
+        ),
+        (
+            Command::SetRowAddress(gddram_row, end_row),
+            FlushStage::RowAddress,
+        ),
+        (Command::WriteRAM, FlushStage::WriteRam),
+    ]
+}
+
+#[cfg(feature = "std")]
+impl<DI, SIZE: DisplaySize, D: DirtyTracker, B: AsMut<[u8]> + AsRef<[u8]>> Ssd1322<DI, SIZE, D, B> {
+    /// Copies the framebuffer into a [`Snapshot`], in the orientation the application draws in.
+    /// In double-buffered mode this is the presented frame.
+    pub fn snapshot(&self) -> Snapshot {
+        let size = self.size();
+        let transposed = self.config.rotation().is_transposed();
+        let pixels = (0..size.height as usize)
+            .flat_map(|y| (0..size.width as usize).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                if transposed {
+                    self.framebuffer.pixel(y, x)
+                } else {
+                    self.framebuffer.pixel(x, y)
                 }
+            })
+            .collect();
+        Snapshot::new(size.width, size.height, pixels).expect("framebuffer levels are 4 bit")
+    }
+}
 
-                self.bounding_box = Some((new_col_addr, new_row_addr));
-            }
-            None => self.bounding_box = Some(([x / 2, x / 2], [y, y])),
+impl<DI, SIZE: DisplaySize, D: DirtyTracker, B> BoundingBox for Ssd1322<DI, SIZE, D, B> {
+    fn update_box(&mut self, x: u16, y: u8) {
+        match &mut self.back {
+            Some(back) => back.update_box(x, y),
+            None => self.framebuffer.update_box(x, y),
         }
     }
 }
 
-impl<DI> DrawTarget for Ssd1322<DI> {
+impl<DI, SIZE: DisplaySize, D: DirtyTracker, B: AsMut<[u8]> + AsRef<[u8]>> DrawTarget
+    for Ssd1322<DI, SIZE, D, B>
+{
     type Color = Gray4;
     type Error = core::convert::Infallible;
 
@@ -175,63 +775,65 @@ impl<DI> DrawTarget for Ssd1322<DI> {
     where
         I: IntoIterator<Item = Pixel<Self::Color>>,
     {
-        for Pixel(coord, color) in pixels.into_iter() {
-            // Check if the pixel coordinates are out of bounds (negative or greater than
-            // (255,63)). `DrawTarget` implementation are required to discard any out of bounds
-            // pixels without returning an error or causing a panic.
-            if let (x @ 0..=255, y @ 0..=63) = (coord.x as usize, coord.y as usize) {
-                // Calculate the index in the framebuffer.
-                let index = (x / 2) + (y * (DISPLAY_WIDTH / 2));
-                let new_val: u8 = if x % 2 == 0 {
-                    update_upper_nibble(self.buffer[index], color.luma())
-                } else {
-                    update_lower_nibble(self.buffer[index], color.luma())
-                };
+        let transposed = self.config.rotation().is_transposed();
+        match &mut self.back {
+            Some(back) => back.draw_iter(pixels, transposed),
+            None => self.framebuffer.draw_iter(pixels, transposed),
+        }
 
-                // Update only if changed
-                if new_val != self.buffer[index] {
-                    self.num_changed += 1;
-                    self.update_box(x as u8, y as u8);
-                    self.buffer[index] = new_val;
-                }
-            }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let transposed = self.config.rotation().is_transposed();
+        match &mut self.back {
+            Some(back) => back.fill_contiguous(area, colors, transposed),
+            None => self.framebuffer.fill_contiguous(area, colors, transposed),
+        }
+
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let transposed = self.config.rotation().is_transposed();
+        match &mut self.back {
+            Some(back) => back.fill_solid(area, color, transposed),
+            None => self.framebuffer.fill_solid(area, color, transposed),
         }
 
         Ok(())
     }
 
     fn clear(&mut self, fill: Self::Color) -> Result<(), Self::Error> {
-        let luma = fill.luma();
-        let byte = (luma << 4) | luma;
-        self.buffer.fill(byte);
+        match &mut self.back {
+            Some(back) => back.clear(fill),
+            None => self.framebuffer.clear(fill),
+        }
 
         Ok(())
     }
 }
 
-impl<DI> OriginDimensions for Ssd1322<DI> {
+impl<DI, SIZE: DisplaySize, D, B> OriginDimensions for Ssd1322<DI, SIZE, D, B> {
     fn size(&self) -> Size {
-        Size::new(
-            DISPLAY_WIDTH.try_into().unwrap(),
-            DISPLAY_HEIGHT.try_into().unwrap(),
-        )
+        if self.config.rotation().is_transposed() {
+            Size::new(SIZE::HEIGHT.into(), SIZE::WIDTH.into())
+        } else {
+            Size::new(SIZE::WIDTH.into(), SIZE::HEIGHT.into())
+        }
     }
 }
 
-#[inline]
-fn update_upper_nibble(input: u8, color: u8) -> u8 {
-    ((color << 4) & 0xF0) | (input & 0x0F)
-}
-
-#[inline]
-fn update_lower_nibble(input: u8, color: u8) -> u8 {
-    color & 0x0F | (input & 0xF0)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dirty::DirtyRows;
+    use crate::size::DisplaySize480x128;
     use display_interface::DataFormat;
+    use display_interface::DisplayError;
     use embedded_graphics::{
         mono_font::{ascii::FONT_6X10, MonoTextStyleBuilder},
         pixelcolor::Gray4,
@@ -239,6 +841,20 @@ mod tests {
     };
     type Result = core::result::Result<(), DisplayError>;
 
+    /// 128x64 panel in the middle of the GDDRAM, exercising a column offset other than the default.
+    struct Panel128x64;
+
+    impl DisplaySize for Panel128x64 {
+        const WIDTH: u16 = 128;
+        const HEIGHT: u16 = 64;
+        const COLUMN_OFFSET: u8 = 0x2C;
+        type Buffer = [u8; 128 * 64 / 2];
+
+        fn new_buffer() -> Self::Buffer {
+            [0; 128 * 64 / 2]
+        }
+    }
+
     pub struct TestInterface1 {}
 
     impl WriteOnlyDataCommand for TestInterface1 {
@@ -279,15 +895,15 @@ mod tests {
             .draw(&mut disp)
             .unwrap();
 
-        assert_eq!(disp.bounding_box.unwrap().0[0], 1);
-        assert_eq!(disp.bounding_box.unwrap().0[1], 1);
-        assert_eq!(disp.bounding_box.unwrap().1[0], 1);
-        assert_eq!(disp.bounding_box.unwrap().1[1], 7);
-        assert_eq!(disp.num_changed, 7);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().0[0], 1);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().0[1], 1);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().1[0], 1);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().1[1], 7);
+        assert_eq!(disp.framebuffer.num_changed, 7);
 
         for i in 1..8 {
             let start = i * 128;
-            assert_eq!(&disp.buffer[start..start + 3], [0, 0xf0, 0]);
+            assert_eq!(&disp.framebuffer.buffer[start..start + 3], [0, 0xf0, 0]);
         }
 
         let _ = disp.flush();
@@ -318,11 +934,11 @@ mod tests {
             .draw(&mut disp)
             .unwrap();
 
-        assert_eq!(disp.bounding_box.unwrap().0[0], 0);
-        assert_eq!(disp.bounding_box.unwrap().0[1], 2);
-        assert_eq!(disp.bounding_box.unwrap().1[0], 1);
-        assert_eq!(disp.bounding_box.unwrap().1[1], 7);
-        assert_eq!(disp.num_changed, 16);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().0[0], 0);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().0[1], 2);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().1[0], 1);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().1[1], 7);
+        assert_eq!(disp.framebuffer.num_changed, 16);
 
         let _ = disp.flush();
     }
@@ -356,11 +972,11 @@ mod tests {
             .draw(&mut disp)
             .unwrap();
 
-        assert_eq!(disp.bounding_box.unwrap().0[0], 0);
-        assert_eq!(disp.bounding_box.unwrap().0[1], 2);
-        assert_eq!(disp.bounding_box.unwrap().1[0], 6);
-        assert_eq!(disp.bounding_box.unwrap().1[1], 12);
-        assert_eq!(disp.num_changed, 16);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().0[0], 0);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().0[1], 2);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().1[0], 6);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().1[1], 12);
+        assert_eq!(disp.framebuffer.num_changed, 16);
 
         let _ = disp.flush();
     }
@@ -389,12 +1005,695 @@ mod tests {
             .draw(&mut disp)
             .unwrap();
 
-        assert_eq!(disp.bounding_box.unwrap().0[0], 127);
-        assert_eq!(disp.bounding_box.unwrap().0[1], 127);
-        assert_eq!(disp.bounding_box.unwrap().1[0], 3);
-        assert_eq!(disp.bounding_box.unwrap().1[1], 7);
-        assert_eq!(disp.num_changed, 5);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().0[0], 127);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().0[1], 127);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().1[0], 3);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().1[1], 7);
+        assert_eq!(disp.framebuffer.num_changed, 5);
 
         let _ = disp.flush();
     }
+
+    #[test]
+    /// Tests the character 'A' clipped at the right edge of a 128x64 panel.
+    fn single_char_clipped_128x64() {
+        let s = TestInterface1 {};
+        let mut disp = Ssd1322::with_size(s, Panel128x64);
+
+        let text_style = MonoTextStyleBuilder::new()
+            .font(&FONT_6X10)
+            .text_color(Gray4::new(0b0000_1111))
+            .build();
+
+        Text::with_baseline("A", Point::new(127, 0), text_style, Baseline::Top)
+            .draw(&mut disp)
+            .unwrap();
+
+        assert_eq!(disp.size(), Size::new(128, 64));
+        assert_eq!(disp.framebuffer.buffer.len(), 128 * 64 / 2);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().0[0], 63);
+        assert_eq!(disp.framebuffer.bounding_box().unwrap().0[1], 63);
+        assert_eq!(disp.framebuffer.num_changed, 5);
+
+        let _ = disp.flush();
+    }
+
+    #[test]
+    /// Tests that 90 degree rotation swaps the axes of the framebuffer and the reported size.
+    fn rotate90_swaps_axes() {
+        let s = TestInterface1 {};
+        let mut disp = Ssd1322::new(s);
+        disp.set_rotation(DisplayRotation::Rotate90).unwrap();
+        disp.flush().unwrap();
+
+        assert_eq!(disp.size(), Size::new(64, 256));
+
+        Pixel(Point::new(3, 200), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+        // Logical (3, 200) lands at framebuffer (200, 3)
+        assert_eq!(disp.framebuffer.buffer[3 * 128 + 100], 0xF0);
+        assert_eq!(
+            disp.framebuffer.bounding_box().unwrap(),
+            ([100, 100], [3, 3])
+        );
+
+        // Out of bounds for the rotated size
+        Pixel(Point::new(64, 0), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+        assert_eq!(disp.framebuffer.num_changed, 1);
+    }
+
+    #[test]
+    /// Tests that changing the rotation marks the whole display as changed.
+    fn rotation_marks_all_dirty() {
+        let s = TestInterface1 {};
+        let mut disp = Ssd1322::new(s);
+        disp.set_rotation(DisplayRotation::Rotate180).unwrap();
+
+        assert_eq!(disp.config().rotation(), DisplayRotation::Rotate180);
+        assert_eq!(disp.size(), Size::new(256, 64));
+        assert_eq!(
+            disp.framebuffer.bounding_box().unwrap(),
+            ([0, 127], [0, 63])
+        );
+    }
+
+    /// Counts the commands and data bytes sent, and records the first opcodes, row addresses and
+    /// start line.
+    #[derive(Default)]
+    pub struct CountingInterface {
+        commands: usize,
+        opcodes: [u8; 16],
+        data_bytes: usize,
+        last_command: u8,
+        row_addresses: [(u8, u8); 4],
+        num_row_addresses: usize,
+        start_line: Option<u8>,
+        /// Last master and contrast currents.
+        currents: (u8, u8),
+    }
+
+    impl WriteOnlyDataCommand for CountingInterface {
+        fn send_commands(&mut self, cmds: DataFormat<'_>) -> Result {
+            if let U8(cmds) = cmds {
+                self.last_command = cmds[0];
+                if let Some(opcode) = self.opcodes.get_mut(self.commands) {
+                    *opcode = cmds[0];
+                }
+            }
+            self.commands += 1;
+            Ok(())
+        }
+
+        fn send_data(&mut self, buf: DataFormat<'_>) -> Result {
+            match buf {
+                U8(slice) => {
+                    match self.last_command {
+                        0x75 => {
+                            self.row_addresses[self.num_row_addresses] = (slice[0], slice[1]);
+                            self.num_row_addresses += 1;
+                        }
+                        0xA1 => self.start_line = Some(slice[0]),
+                        0xC1 => self.currents.1 = slice[0],
+                        0xC7 => self.currents.0 = slice[0],
+                        _ => {}
+                    }
+                    self.data_bytes += slice.len();
+                    Ok(())
+                }
+                _ => Err(DisplayError::DataFormatNotImplemented),
+            }
+        }
+    }
+
+    #[test]
+    /// Tests that distant changes are flushed as separate windows.
+    fn flush_sends_each_dirty_rect() {
+        let mut disp = Ssd1322::<_, DisplaySize256x64, DirtyRects<4>>::with_tracker(
+            CountingInterface::default(),
+        );
+
+        Pixel(Point::new(0, 0), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+        Pixel(Point::new(255, 63), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+        disp.flush().unwrap();
+
+        // Column, row address and 1 row of 2 bytes per window
+        let iface = disp.release();
+        assert_eq!(iface.commands, 2 * 3);
+        assert_eq!(iface.data_bytes, 2 * (4 + 2));
+    }
+
+    #[test]
+    /// Tests that per-row tracking sends one window per changed row span.
+    fn flush_sends_each_dirty_row() {
+        let mut disp = Ssd1322::<_, DisplaySize256x64, DirtyRows<64>>::with_tracker(
+            CountingInterface::default(),
+        );
+
+        Pixel(Point::new(0, 0), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+        Pixel(Point::new(200, 1), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+        disp.flush().unwrap();
+
+        let iface = disp.release();
+        assert_eq!(iface.commands, 2 * 3);
+        assert_eq!(iface.data_bytes, 2 * (4 + 2));
+    }
+
+    #[test]
+    /// Tests that clear marks only the bytes it changed.
+    fn clear_marks_changed_bytes() {
+        let mut disp = Ssd1322::new(TestInterface1 {});
+
+        disp.clear(Gray4::BLACK).unwrap();
+        assert_eq!(disp.framebuffer.bounding_box(), None);
+
+        Pixel(Point::new(20, 30), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+        disp.flush().unwrap();
+        disp.clear(Gray4::BLACK).unwrap();
+        assert_eq!(disp.framebuffer.bounding_box(), Some(([10, 10], [30, 30])));
+        assert_eq!(disp.framebuffer.num_changed, 1);
+
+        disp.clear(Gray4::new(3)).unwrap();
+        assert_eq!(disp.framebuffer.bounding_box(), Some(([0, 127], [0, 63])));
+        assert!(disp.framebuffer.buffer.iter().all(|&byte| byte == 0x33));
+    }
+
+    #[test]
+    /// Tests that repeated clears of the largest panel without a flush don't overflow the
+    /// change counter.
+    fn repeated_clears_of_large_panel() {
+        let mut disp = Ssd1322::with_size(TestInterface1 {}, DisplaySize480x128);
+        for i in 0..6 {
+            disp.clear(Gray4::new(i % 2 * 15)).unwrap();
+        }
+        assert_eq!(disp.framebuffer.num_changed, 5 * 480 * 128);
+        assert_eq!(disp.framebuffer.bounding_box(), Some(([0, 239], [0, 127])));
+    }
+
+    #[test]
+    /// Tests that fill_solid writes the nibbles at odd edges.
+    fn fill_solid_handles_odd_edges() {
+        let mut disp = Ssd1322::new(TestInterface1 {});
+
+        disp.fill_solid(
+            &Rectangle::new(Point::new(3, 2), Size::new(4, 2)),
+            Gray4::WHITE,
+        )
+        .unwrap();
+
+        assert_eq!(disp.framebuffer.num_changed, 8);
+        assert_eq!(disp.framebuffer.bounding_box(), Some(([1, 3], [2, 3])));
+        for row in 2..4 {
+            let start = row * 128;
+            assert_eq!(
+                &disp.framebuffer.buffer[start..start + 5],
+                [0, 0x0F, 0xFF, 0xF0, 0]
+            );
+        }
+
+        // Clipped at the bottom right corner
+        disp.fill_solid(
+            &Rectangle::new(Point::new(254, 62), Size::new(10, 10)),
+            Gray4::WHITE,
+        )
+        .unwrap();
+        assert_eq!(disp.framebuffer.num_changed, 12);
+    }
+
+    #[test]
+    /// Tests that fill_contiguous writes the same pixels as drawing them one by one, including
+    /// odd edges, clipping, rotation and short color iterators.
+    fn fill_contiguous_matches_draw_iter() {
+        let areas = [
+            Rectangle::new(Point::new(3, 2), Size::new(5, 3)),
+            Rectangle::new(Point::new(0, 0), Size::new(256, 64)),
+            Rectangle::new(Point::new(-3, -2), Size::new(10, 5)),
+            Rectangle::new(Point::new(250, 60), Size::new(11, 10)),
+            Rectangle::new(Point::new(1, 1), Size::new(1, 1)),
+        ];
+
+        for rotation in [DisplayRotation::Rotate0, DisplayRotation::Rotate90] {
+            for area in areas {
+                for count in [area.size.width * area.size.height, 12] {
+                    let colors = (0..count).map(|i| Gray4::new((i % 15 + 1) as u8));
+
+                    let mut fast = Ssd1322::new(TestInterface1 {});
+                    fast.set_rotation(rotation).unwrap();
+                    fast.flush().unwrap();
+                    fast.fill_contiguous(&area, colors.clone()).unwrap();
+
+                    let mut slow = Ssd1322::new(TestInterface1 {});
+                    slow.set_rotation(rotation).unwrap();
+                    slow.flush().unwrap();
+                    slow.draw_iter(area.points().zip(colors).map(|(p, c)| Pixel(p, c)))
+                        .unwrap();
+
+                    assert_eq!(fast.framebuffer.buffer, slow.framebuffer.buffer);
+                    assert_eq!(fast.framebuffer.num_changed, slow.framebuffer.num_changed);
+                    assert_eq!(
+                        fast.framebuffer.bounding_box(),
+                        slow.framebuffer.bounding_box()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    /// Tests that scrolling moves the start line, rotates the framebuffer and flushes the rows
+    /// scrolled into view at their wrapped GDDRAM rows.
+    fn scroll_rotates_rows() {
+        let mut disp = Ssd1322::new(CountingInterface::default());
+        Pixel(Point::new(0, 20), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+
+        disp.scroll_to(16).unwrap();
+        assert_eq!(disp.scroll_position(), 16);
+        assert_eq!(disp.framebuffer.buffer[4 * 128], 0xF0);
+        assert_eq!(disp.framebuffer.bounding_box(), Some(([0, 127], [48, 63])));
+
+        // Back past the start, which wraps around the GDDRAM
+        disp.scroll_by(-20).unwrap();
+        assert_eq!(disp.scroll_position(), 124);
+        assert_eq!(disp.framebuffer.buffer[24 * 128], 0xF0);
+        assert_eq!(disp.framebuffer.bounding_box(), Some(([0, 127], [0, 19])));
+        disp.flush().unwrap();
+
+        let iface = disp.release();
+        assert_eq!(iface.start_line, Some(124));
+        // The pixel, rows 48-63 after the first scroll, then rows 0-19 split at the wrap
+        assert_eq!(
+            iface.row_addresses[..iface.num_row_addresses],
+            [(20, 20), (64, 79), (124, 127), (0, 15)]
+        );
+    }
+
+    /// Records the levels set on the reset pin.
+    #[derive(Default)]
+    pub struct TestPin {
+        levels: [bool; 2],
+        count: usize,
+    }
+
+    impl embedded_hal::digital::ErrorType for TestPin {
+        type Error = core::convert::Infallible;
+    }
+
+    impl OutputPin for TestPin {
+        fn set_low(&mut self) -> core::result::Result<(), Self::Error> {
+            self.levels[self.count] = false;
+            self.count += 1;
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> core::result::Result<(), Self::Error> {
+            self.levels[self.count] = true;
+            self.count += 1;
+            Ok(())
+        }
+    }
+
+    /// Accumulates the requested delay.
+    #[derive(Default)]
+    pub struct TestDelay {
+        total_ns: u64,
+    }
+
+    impl DelayNs for TestDelay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.total_ns += u64::from(ns);
+        }
+    }
+
+    #[test]
+    /// Tests that flushes are limited to the partial display rows and that exiting sends the
+    /// rows that were off.
+    fn partial_display_clips_flush() {
+        let mut disp = Ssd1322::new(CountingInterface::default());
+        assert!(matches!(
+            disp.enter_partial_display(10..=64),
+            Err(Error::InvalidRows)
+        ));
+
+        disp.enter_partial_display(0..=9).unwrap();
+        assert_eq!(disp.partial_display(), Some(0..=9));
+        disp.fill_solid(
+            &Rectangle::new(Point::new(0, 5), Size::new(4, 10)),
+            Gray4::WHITE,
+        )
+        .unwrap();
+        disp.flush().unwrap();
+
+        disp.exit_partial_display().unwrap();
+        assert_eq!(disp.partial_display(), None);
+        assert_eq!(disp.framebuffer.bounding_box(), Some(([0, 127], [10, 63])));
+
+        // A re-init drives all rows again
+        disp.enter_partial_display(0..=9).unwrap();
+        disp.init().unwrap();
+        assert_eq!(disp.partial_display(), None);
+        assert_eq!(disp.framebuffer.bounding_box(), Some(([0, 127], [10, 63])));
+
+        // The flushed rows, then the row window of the init sequence
+        let iface = disp.release();
+        assert_eq!(
+            iface.row_addresses[..iface.num_row_addresses],
+            [(5, 9), (0, 63)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "WIDTH * HEIGHT / 2 bytes")]
+    fn mismatched_buffer_is_rejected() {
+        /// 128x64 panel with the buffer of a 128x32 one.
+        struct ShortBuffer;
+
+        impl DisplaySize for ShortBuffer {
+            const WIDTH: u16 = 128;
+            const HEIGHT: u16 = 64;
+            const COLUMN_OFFSET: u8 = 0x2C;
+            type Buffer = [u8; 128 * 32 / 2];
+
+            fn new_buffer() -> Self::Buffer {
+                [0; 128 * 32 / 2]
+            }
+        }
+
+        Ssd1322::with_size(TestInterface1 {}, ShortBuffer);
+    }
+
+    #[test]
+    /// Tests that the framebuffer can live in caller-provided storage.
+    fn framebuffer_in_supplied_buffer() {
+        let mut storage = [0xAAu8; 128 * 64 / 2 + 1];
+        assert!(matches!(
+            Ssd1322::with_buffer(TestInterface1 {}, Panel128x64, &mut storage[..]),
+            Err(Error::BufferSize)
+        ));
+
+        let mut disp =
+            Ssd1322::with_buffer(TestInterface1 {}, Panel128x64, &mut storage[1..]).unwrap();
+        Pixel(Point::new(2, 1), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+        disp.flush().unwrap();
+
+        assert_eq!(storage[1 + 64 + 1], 0xF0);
+        assert_eq!(storage[1..].iter().filter(|&&byte| byte != 0).count(), 1);
+    }
+
+    #[test]
+    /// Tests that drawing goes to the back buffer until presented.
+    fn double_buffering_presents_changes() {
+        extern crate std;
+        use std::{boxed::Box, vec};
+        let leak = |len| Box::leak(vec![0u8; len].into_boxed_slice());
+
+        let mut disp = Ssd1322::with_size(CountingInterface::default(), Panel128x64);
+        assert!(matches!(
+            disp.enable_double_buffering(leak(100)),
+            Err(Error::BufferSize)
+        ));
+        disp.enable_double_buffering(leak(128 * 64 / 2)).unwrap();
+        assert!(disp.is_double_buffered());
+
+        let area = Rectangle::new(Point::new(4, 2), Size::new(8, 3));
+        disp.fill_solid(&area, Gray4::WHITE).unwrap();
+        disp.flush().unwrap();
+        assert_eq!(disp.display.data_bytes, 0);
+        assert_eq!(disp.framebuffer.buffer[2 * 64 + 2], 0x00);
+
+        disp.present().unwrap();
+        assert_eq!(disp.framebuffer.buffer[2 * 64 + 2], 0xFF);
+        // Pixels 4..=11 in rows 2..=4, 4 bytes per row plus the row and column addresses
+        assert_eq!(disp.display.data_bytes, 3 * 4 + 4);
+
+        // Redrawing the same frame sends nothing
+        disp.clear(Gray4::BLACK).unwrap();
+        disp.fill_solid(&area, Gray4::WHITE).unwrap();
+        disp.present().unwrap();
+        assert_eq!(disp.display.data_bytes, 3 * 4 + 4);
+
+        // Changes not presented yet are kept when leaving double buffering
+        disp.fill_solid(&area, Gray4::new(3)).unwrap();
+        let back = disp.disable_double_buffering().unwrap();
+        assert_eq!(back[2 * 64 + 2], 0x33);
+        assert_eq!(disp.framebuffer.buffer[2 * 64 + 2], 0x33);
+        assert_eq!(disp.framebuffer.bounding_box(), Some(([2, 5], [2, 4])));
+    }
+
+    #[test]
+    /// Tests that a re-init moves the back buffer back to the start of the GDDRAM with the
+    /// presented frame, and has the whole back buffer compared on the next present.
+    fn init_resets_back_buffer() {
+        extern crate std;
+        use std::{boxed::Box, vec};
+
+        let mut disp = Ssd1322::with_size(CountingInterface::default(), Panel128x64);
+        disp.enable_double_buffering(Box::leak(vec![0u8; 128 * 64 / 2].into_boxed_slice()))
+            .unwrap();
+        disp.scroll_to(8).unwrap();
+        disp.present().unwrap();
+
+        disp.init().unwrap();
+        let back = disp.back.as_ref().unwrap();
+        assert_eq!((disp.framebuffer.scroll, back.scroll), (0, 0));
+        assert_eq!(back.bounding_box(), Some(([0, 63], [0, 63])));
+    }
+
+    #[test]
+    /// Tests the command sequences of the power state transitions.
+    fn power_states_sequence_commands() {
+        let mut disp = Ssd1322::new(CountingInterface::default());
+        assert_eq!(disp.power_state(), PowerState::Sleep);
+
+        disp.set_power_state(PowerState::Dim).unwrap();
+        disp.set_power_state(PowerState::Dim).unwrap();
+        disp.set_power_state(PowerState::Off).unwrap();
+        // Commands of the GDDRAM restore
+        let restore = [0x15, 0x75, 0x5C];
+        disp.set_power_state(PowerState::On).unwrap();
+        disp.sleep().unwrap();
+        disp.wake(false).unwrap();
+        assert_eq!(disp.power_state(), PowerState::On);
+
+        let iface = disp.release();
+        assert_eq!(iface.commands, 13);
+        assert_eq!(iface.opcodes[..5], [0xC7, 0xAF, 0xAE, 0xAB, 0xAB]);
+        assert_eq!(iface.opcodes[5..8], restore);
+        assert_eq!(iface.opcodes[8..], [0xC7, 0xAF, 0xAE, 0xC7, 0xAF, 0, 0, 0]);
+        assert_eq!(iface.data_bytes, 1 + 1 + 1 + 4 + 256 * 64 / 2 + 1 + 1);
+    }
+
+    #[test]
+    fn brightness_sets_currents() {
+        let mut disp = Ssd1322::new(CountingInterface::default());
+        disp.set_brightness(128).unwrap();
+        assert_eq!(disp.brightness(), 128);
+        assert_eq!(
+            (
+                disp.config().master_current(),
+                disp.config().contrast_current()
+            ),
+            brightness::currents(128)
+        );
+
+        // Dimmed, the master current only changes with the dim current
+        disp.set_power_state(PowerState::Dim).unwrap();
+        disp.set_brightness(255).unwrap();
+
+        let mut delay = TestDelay::default();
+        disp.fade_brightness(0, 500, &mut delay).unwrap();
+        assert_eq!(disp.brightness(), 0);
+        assert!(delay.total_ns <= 500_000_000 && delay.total_ns > 490_000_000);
+
+        let iface = disp.release();
+        assert_eq!(
+            iface.opcodes[..7],
+            [0xC1, 0xC7, 0xC7, 0xAF, 0xC1, 0xC7, 0xC1]
+        );
+    }
+
+    #[test]
+    fn dimming_never_brightens() {
+        let output =
+            |(master, contrast): (u8, u8)| (u32::from(master) + 1) * (u32::from(contrast) + 1);
+        let mut disp = Ssd1322::new(CountingInterface::default());
+        for level in 0..=brightness::MAX_BRIGHTNESS {
+            disp.set_power_state(PowerState::On).unwrap();
+            disp.set_brightness(level).unwrap();
+            let on = output(disp.interface().currents);
+            disp.set_power_state(PowerState::Dim).unwrap();
+            let dim = output(disp.interface().currents);
+            assert!(dim <= on, "level {}: dim {} > on {}", level, dim, on);
+
+            // Changing the brightness while dimmed follows the same rule
+            disp.set_brightness(level).unwrap();
+            assert_eq!(output(disp.interface().currents), dim);
+        }
+    }
+
+    #[test]
+    fn display_mode_is_tracked() {
+        let mut disp = Ssd1322::new(CountingInterface::default());
+        assert_eq!(disp.display_mode(), DisplayMode::Normal);
+
+        disp.set_display_mode(DisplayMode::Inverse).unwrap();
+        disp.set_display_mode(DisplayMode::Inverse).unwrap();
+        disp.set_display_mode(DisplayMode::AllOn).unwrap();
+        disp.set_display_mode(DisplayMode::AllOff).unwrap();
+        disp.set_display_mode(DisplayMode::Normal).unwrap();
+        assert_eq!(disp.display_mode(), DisplayMode::Normal);
+
+        let iface = disp.release();
+        assert_eq!(iface.opcodes[..iface.commands], [0xA7, 0xA5, 0xA4, 0xA6]);
+    }
+
+    #[test]
+    fn burn_in_protection_shifts_and_saves_screen() {
+        let mut disp = Ssd1322::new(CountingInterface::default());
+        disp.init().unwrap();
+        disp.enable_burn_in_protection(BurnInProtection {
+            shift_interval_ms: 1000,
+            max_shift: 1,
+            idle_timeout_ms: Some(2500),
+            screen_saver: PowerState::Sleep,
+        });
+        disp.fill_solid(
+            &Rectangle::new(Point::zero(), Size::new(32, 16)),
+            Gray4::WHITE,
+        )
+        .unwrap();
+
+        for _ in 0..3 {
+            disp.tick(1000).unwrap();
+        }
+        assert_eq!(disp.power_state(), PowerState::Sleep);
+        // Not lit while the screen saver is on
+        disp.tick(1000).unwrap();
+        assert_eq!(disp.on_time().unwrap()[0][0], 3000);
+        assert_eq!(disp.on_time().unwrap()[0][1], 0);
+
+        disp.report_activity().unwrap();
+        assert_eq!(disp.power_state(), PowerState::On);
+        disp.disable_burn_in_protection().unwrap();
+        assert!(disp.on_time().is_none());
+
+        let iface = disp.release();
+        // Init, the window of the row above the image blanked before the first of 4 shifts,
+        // sleep, wake with the master current and the offset reset
+        assert_eq!(iface.commands, 22 + 3 + 4 + 1 + 2 + 1);
+    }
+
+    #[test]
+    /// Tests that a re-init restarts the shift from the configured display offset and stops the
+    /// screen saver, keeping the on-time.
+    fn init_restarts_burn_in_protection() {
+        let mut disp = Ssd1322::new(CountingInterface::default());
+        disp.init().unwrap();
+        disp.enable_burn_in_protection(BurnInProtection {
+            shift_interval_ms: 1000,
+            max_shift: 2,
+            idle_timeout_ms: Some(1500),
+            screen_saver: PowerState::Dim,
+        });
+        Pixel(Point::zero(), Gray4::WHITE).draw(&mut disp).unwrap();
+        disp.tick(1000).unwrap();
+        disp.tick(1000).unwrap();
+        assert_eq!(disp.power_state(), PowerState::Dim);
+        let on_time = *disp.on_time().unwrap();
+
+        disp.init().unwrap();
+        let mut burn_in = disp.burn_in.clone().unwrap();
+        assert_eq!(burn_in.resume, None);
+        assert_eq!(burn_in.idle_ms, 0);
+        assert_eq!(burn_in.on_time, on_time);
+        assert_eq!(burn_in.next_shift(1000), Some(1));
+    }
+
+    #[test]
+    fn smooth_scroll_steps_one_line() {
+        let mut disp = Ssd1322::new(CountingInterface::default());
+        let mut delay = TestDelay::default();
+
+        disp.smooth_scroll(-3, 20, &mut delay).unwrap();
+
+        assert_eq!(disp.scroll_position(), 125);
+        assert_eq!(delay.total_ns, 3 * 20_000_000);
+        let iface = disp.release();
+        assert_eq!(iface.num_row_addresses, 3);
+    }
+
+    #[test]
+    fn reset_pulses_pin() {
+        let mut disp = Ssd1322::new(TestInterface1 {});
+        let mut rst = TestPin::default();
+        let mut delay = TestDelay::default();
+
+        disp.reset(&mut rst, &mut delay).unwrap();
+
+        assert_eq!(rst.count, 2);
+        assert_eq!(rst.levels, [false, true]);
+        assert_eq!(delay.total_ns, 210_000_000);
+    }
+
+    /// Accepts commands but fails every data transfer.
+    pub struct FailingDataInterface {}
+
+    impl WriteOnlyDataCommand for FailingDataInterface {
+        fn send_commands(&mut self, _cmds: DataFormat<'_>) -> Result {
+            Ok(())
+        }
+
+        fn send_data(&mut self, _buf: DataFormat<'_>) -> Result {
+            Err(DisplayError::BusWriteError)
+        }
+    }
+
+    #[test]
+    fn errors_report_failed_stage() {
+        let mut disp = Ssd1322::new(FailingDataInterface {});
+
+        assert!(matches!(
+            disp.init(),
+            Err(Error::Command {
+                opcode: 0xFD,
+                source: DisplayError::BusWriteError
+            })
+        ));
+
+        Pixel(Point::new(10, 5), Gray4::WHITE)
+            .draw(&mut disp)
+            .unwrap();
+        assert!(matches!(
+            disp.flush(),
+            Err(Error::Flush {
+                stage: FlushStage::ColumnAddress,
+                row: 5,
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            disp.flush_all(),
+            Err(Error::Flush {
+                stage: FlushStage::ColumnAddress,
+                row: 0,
+                ..
+            })
+        ));
+    }
 }
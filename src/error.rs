@@ -0,0 +1,55 @@
+//! Driver errors
+use display_interface::DisplayError;
+
+/// Part of a flush that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushStage {
+    /// Setting the column address window.
+    ColumnAddress,
+    /// Setting the row address window.
+    RowAddress,
+    /// Enabling the GDDRAM write.
+    WriteRam,
+    /// Sending the pixel data.
+    Data,
+}
+
+/// Errors reported by the driver.
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// Sending the command with the given command byte failed.
+    Command {
+        /// Command byte of the failed command.
+        opcode: u8,
+        /// Error reported by the display interface.
+        source: DisplayError,
+    },
+    /// Sending the framebuffer to the display failed.
+    Flush {
+        /// Stage of the flush that failed.
+        stage: FlushStage,
+        /// First framebuffer row of the failed transfer.
+        row: u8,
+        /// Error reported by the display interface.
+        source: DisplayError,
+    },
+    /// Driving the reset pin failed.
+    Reset,
+    /// The requested rows are empty or outside the panel.
+    InvalidRows,
+    /// The supplied buffer does not have the size of the framebuffer.
+    BufferSize,
+    /// The area to draw does not cover whole column addresses of 4 pixels, which can't be
+    /// written without a framebuffer.
+    Unaligned,
+}
+
+impl Error {
+    /// Returns the error reported by the display interface, if any.
+    pub fn display_error(&self) -> Option<&DisplayError> {
+        match self {
+            Error::Command { source, .. } | Error::Flush { source, .. } => Some(source),
+            Error::Reset | Error::InvalidRows | Error::BufferSize | Error::Unaligned => None,
+        }
+    }
+}
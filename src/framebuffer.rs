@@ -0,0 +1,494 @@
+//! Framebuffer and change tracking shared by the blocking and async drivers
+use crate::dirty::{DirtyRegion, DirtyTracker};
+use crate::size::{DisplaySize, Geometry, GDDRAM_ROWS};
+use crate::window::Window;
+use core::marker::PhantomData;
+use embedded_graphics::{pixelcolor::Gray4, prelude::*, primitives::Rectangle, Pixel};
+
+/// Provides an optimized way to capture changes to the framebuffer.
+pub trait BoundingBox {
+    /// Updates the bounding_box field to the modified area. The bounding_box unit is in bytes.
+    fn update_box(&mut self, x: u16, y: u8);
+}
+
+/// Packed 4 bit framebuffer with two pixels per byte, the left pixel in the upper nibble.
+///
+/// The bytes are held in `B`, the inline array of the panel size unless storage is supplied.
+pub(crate) struct FrameBuffer<SIZE: DisplaySize, D, B = <SIZE as DisplaySize>::Buffer> {
+    pub(crate) buffer: B,
+    pub(crate) dirty: D,
+    pub(crate) num_changed: usize,
+    /// GDDRAM row holding row 0 of the framebuffer, moved by scrolling.
+    pub(crate) scroll: u8,
+    /// Rows driven by the display, less than all of them in partial display mode.
+    pub(crate) visible_rows: (u8, u8),
+    size: PhantomData<SIZE>,
+}
+
+impl<SIZE: DisplaySize, D: DirtyTracker> FrameBuffer<SIZE, D> {
+    pub(crate) fn new() -> Self {
+        Self::with_buffer(SIZE::new_buffer())
+    }
+}
+
+impl<SIZE: DisplaySize, D: DirtyTracker, B: AsRef<[u8]> + AsMut<[u8]>> FrameBuffer<SIZE, D, B> {
+    /// Number of framebuffer bytes in one row of pixels.
+    pub(crate) const ROW_BYTES: usize = SIZE::WIDTH as usize / 2;
+
+    /// Number of framebuffer bytes.
+    pub(crate) const BYTES: usize = SIZE::WIDTH as usize * SIZE::HEIGHT as usize / 2;
+
+    /// Fails the build when the dirty tracker can't record every row of the panel.
+    const TRACKS_ALL_ROWS: () = assert!(
+        D::MAX_ROWS >= SIZE::HEIGHT as usize,
+        "the dirty tracker records fewer rows than the panel has"
+    );
+
+    /// Creates a framebuffer in `buffer`, which must hold [`BYTES`](Self::BYTES) bytes.
+    pub(crate) fn with_buffer(buffer: B) -> Self {
+        let () = Geometry::<SIZE>::CHECK;
+        let () = Self::TRACKS_ALL_ROWS;
+        assert_eq!(
+            buffer.as_ref().len(),
+            Self::BYTES,
+            "the framebuffer must hold WIDTH * HEIGHT / 2 bytes"
+        );
+        Self {
+            buffer,
+            dirty: D::default(),
+            num_changed: 0,
+            scroll: 0,
+            visible_rows: (0, (SIZE::HEIGHT - 1) as u8),
+            size: PhantomData,
+        }
+    }
+
+    /// Returns the bytes of `row` inside `window`.
+    pub(crate) fn row_bytes(&self, window: &Window, row: u8) -> &[u8] {
+        let start = window.first_byte + row as usize * Self::ROW_BYTES;
+        &self.buffer.as_ref()[start..start + window.num_bytes]
+    }
+
+    /// Returns the bytes of the full rows `rows.0..=rows.1`.
+    pub(crate) fn rows_bytes(&self, rows: (u8, u8)) -> &[u8] {
+        let start = usize::from(rows.0) * Self::ROW_BYTES;
+        let end = (usize::from(rows.1) + 1) * Self::ROW_BYTES;
+        &self.buffer.as_ref()[start..end]
+    }
+
+    /// Splits `window` where its GDDRAM rows wrap around, returning each part with the GDDRAM
+    /// row of its first row.
+    pub(crate) fn segments(&self, window: Window) -> impl Iterator<Item = (Window, u8)> {
+        window.segments(self.scroll)
+    }
+
+    /// Returns the windows covering all changes in the visible rows since the last flush, or the
+    /// visible part of the full window if sending that is cheaper.
+    pub(crate) fn dirty_windows(&self) -> impl Iterator<Item = Window> + '_ {
+        let visible_rows = self.visible_rows;
+        let windows = move || {
+            self.dirty
+                .regions()
+                .filter_map(move |region| Window::from_region(region).clip_rows(visible_rows))
+        };
+        let full_window = Self::full_window().clip_rows(visible_rows);
+        let cost: usize = windows().map(|window| window.cost()).sum();
+        let full = full_window.is_some_and(|window| cost >= window.cost());
+
+        full_window
+            .filter(|_| full)
+            .into_iter()
+            .chain(windows().filter(move |_| !full))
+    }
+
+    /// Returns the bounding box of all changes in bytes, as `([first, last] column, [first,
+    /// last] row)`.
+    #[cfg(test)]
+    pub(crate) fn bounding_box(&self) -> Option<([u8; 2], [u8; 2])> {
+        self.dirty
+            .regions()
+            .reduce(|a, b| a.union(&b))
+            .map(|region| {
+                (
+                    [region.columns.0, region.columns.1],
+                    [region.rows.0, region.rows.1],
+                )
+            })
+    }
+
+    /// Returns the window covering the whole framebuffer.
+    pub(crate) fn full_window() -> Window {
+        Window {
+            first_byte: 0,
+            num_bytes: Self::ROW_BYTES,
+            rows: (0, (SIZE::HEIGHT - 1) as u8),
+        }
+    }
+
+    /// Forgets the changes after they have been sent to the display.
+    pub(crate) fn reset_dirty(&mut self) {
+        self.dirty.reset();
+        self.num_changed = 0;
+    }
+
+    /// Marks the whole framebuffer as changed.
+    pub(crate) fn mark_all_dirty(&mut self) {
+        self.mark_rows_dirty((0, (SIZE::HEIGHT - 1) as u8));
+    }
+
+    /// Marks the full rows `rows.0..=rows.1` as changed.
+    pub(crate) fn mark_rows_dirty(&mut self, rows: (u8, u8)) {
+        self.dirty.mark_region(DirtyRegion {
+            columns: (0, (Self::ROW_BYTES - 1) as u8),
+            rows,
+        });
+    }
+
+    /// Changes the rows driven by the display, marking the rows that were hidden until now as
+    /// changed since their changes were not flushed.
+    pub(crate) fn set_visible_rows(&mut self, rows: (u8, u8)) {
+        let (first, last) = self.visible_rows;
+        if first > 0 {
+            self.mark_rows_dirty((0, first - 1));
+        }
+        if u16::from(last) + 1 < SIZE::HEIGHT {
+            self.mark_rows_dirty((last + 1, (SIZE::HEIGHT - 1) as u8));
+        }
+        self.visible_rows = rows;
+    }
+
+    /// Moves the GDDRAM row of framebuffer row 0 forward by `lines`, wrapping at the end of the
+    /// GDDRAM.
+    ///
+    /// The framebuffer rows are rotated so that they keep matching the rows that stay on the
+    /// display, whichever scroll direction keeps more of them, and the rows scrolled into view
+    /// are marked as changed. Pending changes must have been flushed before.
+    pub(crate) fn scroll_by(&mut self, lines: u8) {
+        let up = lines % GDDRAM_ROWS;
+        if up == 0 {
+            return;
+        }
+        let down = GDDRAM_ROWS - up;
+        self.scroll = (self.scroll + up) % GDDRAM_ROWS;
+
+        let height = SIZE::HEIGHT as u8;
+        if up <= down && up < height {
+            self.buffer
+                .as_mut()
+                .rotate_left(usize::from(up) * Self::ROW_BYTES);
+            self.mark_rows_dirty((height - up, height - 1));
+        } else if down < height {
+            self.buffer
+                .as_mut()
+                .rotate_right(usize::from(down) * Self::ROW_BYTES);
+            self.mark_rows_dirty((0, down - 1));
+        } else {
+            self.mark_all_dirty();
+        }
+    }
+
+    /// Moves framebuffer row 0 back to GDDRAM row 0 after the controller has been initialized,
+    /// marking everything as changed if the GDDRAM still holds the rows at their scrolled
+    /// position.
+    pub(crate) fn reset_scroll(&mut self) {
+        if self.scroll != 0 {
+            self.scroll = 0;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// Copies the bytes changed in `back` since its last copy, marking the ones that differ from
+    /// this framebuffer as changed.
+    pub(crate) fn copy_changes<B2: AsRef<[u8]> + AsMut<[u8]>>(
+        &mut self,
+        back: &mut FrameBuffer<SIZE, D, B2>,
+    ) {
+        for region in back.dirty.regions() {
+            let mut spans = RowSpans::default();
+            for y in region.rows.0..=region.rows.1 {
+                let mut changed = None;
+                for column in region.columns.0..=region.columns.1 {
+                    let index = usize::from(y) * Self::ROW_BYTES + usize::from(column);
+                    if self.write_byte(index, back.buffer.as_ref()[index], 0xFF) {
+                        changed = Some(widen(changed, column));
+                    }
+                }
+                spans.push(&mut self.dirty, y, changed);
+            }
+            spans.finish(&mut self.dirty);
+        }
+        back.reset_dirty();
+    }
+
+    /// Draws pixels, swapping the axes if `transposed` is set.
+    pub(crate) fn draw_iter<I>(&mut self, pixels: I, transposed: bool)
+    where
+        I: IntoIterator<Item = Pixel<Gray4>>,
+    {
+        for Pixel(coord, color) in pixels.into_iter() {
+            // 90 and 270 degree rotations swap the axes, the remaining flip is done by the
+            // hardware remap.
+            let coord = if transposed {
+                Point::new(coord.y, coord.x)
+            } else {
+                coord
+            };
+
+            // Check if the pixel coordinates are out of bounds (negative or greater than
+            // the panel size). `DrawTarget` implementation are required to discard any out of
+            // bounds pixels without returning an error or causing a panic.
+            if coord.x < 0
+                || coord.y < 0
+                || coord.x >= i32::from(SIZE::WIDTH)
+                || coord.y >= i32::from(SIZE::HEIGHT)
+            {
+                continue;
+            }
+            let (x, y) = (coord.x as usize, coord.y as usize);
+
+            // Calculate the index in the framebuffer.
+            let index = (x / 2) + (y * Self::ROW_BYTES);
+            let old_val = self.buffer.as_ref()[index];
+            let new_val: u8 = if x % 2 == 0 {
+                update_upper_nibble(old_val, color.luma())
+            } else {
+                update_lower_nibble(old_val, color.luma())
+            };
+
+            // Update only if changed
+            if new_val != old_val {
+                self.num_changed = self.num_changed.saturating_add(1);
+                self.update_box(x as u16, y as u8);
+                self.buffer.as_mut()[index] = new_val;
+            }
+        }
+    }
+
+    /// Fills the whole framebuffer with `fill` and marks the changed bytes.
+    pub(crate) fn clear(&mut self, fill: Gray4) {
+        self.fill_solid(&self.bounds(), fill, false);
+    }
+
+    /// Fills `area` with `color` a byte at a time, swapping the axes if `transposed` is set,
+    /// and marks the changed bytes.
+    pub(crate) fn fill_solid(&mut self, area: &Rectangle, color: Gray4, transposed: bool) {
+        let area = if transposed {
+            Rectangle::new(
+                Point::new(area.top_left.y, area.top_left.x),
+                Size::new(area.size.height, area.size.width),
+            )
+        } else {
+            *area
+        };
+        let area = area.intersection(&self.bounds());
+        let bottom_right = match area.bottom_right() {
+            Some(bottom_right) => bottom_right,
+            None => return,
+        };
+        let (x0, y0) = (area.top_left.x as usize, area.top_left.y as usize);
+        let (x1, y1) = (bottom_right.x as usize, bottom_right.y as usize);
+
+        let luma = color.luma();
+        let byte = (luma << 4) | luma;
+        let mut spans = RowSpans::default();
+        for y in y0..=y1 {
+            let mut changed = None;
+            for column in x0 / 2..=x1 / 2 {
+                if self.write_byte(
+                    y * Self::ROW_BYTES + column,
+                    byte,
+                    span_mask(column, x0, x1),
+                ) {
+                    changed = Some(widen(changed, column as u8));
+                }
+            }
+            spans.push(&mut self.dirty, y as u8, changed);
+        }
+        spans.finish(&mut self.dirty);
+    }
+
+    /// Fills `area` with `colors` in row-major order, packing two pixels per byte, and marks
+    /// the changed bytes.
+    ///
+    /// Colors of pixels outside the framebuffer are skipped, and filling stops early if `colors`
+    /// runs out.
+    pub(crate) fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I, transposed: bool)
+    where
+        I: IntoIterator<Item = Gray4>,
+    {
+        if transposed {
+            // Logical rows are framebuffer columns, so bytes can't be packed.
+            let pixels = area.points().zip(colors).map(|(p, c)| Pixel(p, c));
+            return self.draw_iter(pixels, true);
+        }
+
+        let clipped = area.intersection(&self.bounds());
+        let bottom_right = match clipped.bottom_right() {
+            Some(bottom_right) => bottom_right,
+            None => return,
+        };
+        let (x0, x1) = (clipped.top_left.x as usize, bottom_right.x as usize);
+        let skip_left = (clipped.top_left.x - area.top_left.x) as usize;
+        let width = area.size.width as usize;
+
+        let mut colors = colors.into_iter();
+        let mut spans = RowSpans::default();
+        for y in area.rows() {
+            let mut row_colors = colors.by_ref().take(width);
+            if y < clipped.top_left.y || y > bottom_right.y {
+                if row_colors.count() < width {
+                    break;
+                }
+                continue;
+            }
+            if skip_left > 0 && row_colors.nth(skip_left - 1).is_none() {
+                break;
+            }
+
+            let y = y as usize;
+            let mut changed = None;
+            let mut exhausted = false;
+            let mut x = x0;
+            while x <= x1 {
+                let column = x / 2;
+                let Some(first) = row_colors.next() else {
+                    exhausted = true;
+                    break;
+                };
+                let (byte, mask) = if x % 2 == 1 {
+                    // Single pixel at an odd left edge
+                    x += 1;
+                    (first.luma(), 0x0F)
+                } else if let Some(second) = row_colors.next().filter(|_| x < x1) {
+                    x += 2;
+                    ((first.luma() << 4) | second.luma(), 0xFF)
+                } else {
+                    // Single pixel at an even right edge, or the last color
+                    exhausted = x < x1;
+                    x += 2;
+                    (first.luma() << 4, 0xF0)
+                };
+
+                if self.write_byte(y * Self::ROW_BYTES + column, byte, mask) {
+                    changed = Some(widen(changed, column as u8));
+                }
+            }
+            spans.push(&mut self.dirty, y as u8, changed);
+            if exhausted {
+                break;
+            }
+
+            // Skip the colors clipped at the right
+            row_colors.for_each(drop);
+        }
+        spans.finish(&mut self.dirty);
+    }
+
+    /// Writes the nibbles of `byte` selected by `mask` at `index`, returning true if the byte
+    /// changed.
+    fn write_byte(&mut self, index: usize, byte: u8, mask: u8) -> bool {
+        let old_val = self.buffer.as_ref()[index];
+        let new_val = (old_val & !mask) | (byte & mask);
+        if new_val == old_val {
+            return false;
+        }
+
+        let diff = old_val ^ new_val;
+        let nibbles = usize::from(diff & 0xF0 != 0) + usize::from(diff & 0x0F != 0);
+        self.num_changed = self.num_changed.saturating_add(nibbles);
+        self.buffer.as_mut()[index] = new_val;
+        true
+    }
+
+    /// Gray level of the pixel at `x`, `y`.
+    #[cfg(feature = "std")]
+    pub(crate) fn pixel(&self, x: usize, y: usize) -> u8 {
+        let byte = self.buffer.as_ref()[x / 2 + y * Self::ROW_BYTES];
+        if x % 2 == 1 {
+            byte & 0x0F
+        } else {
+            byte >> 4
+        }
+    }
+
+    /// Area of the framebuffer in pixels.
+    fn bounds(&self) -> Rectangle {
+        Rectangle::new(
+            Point::zero(),
+            Size::new(SIZE::WIDTH.into(), SIZE::HEIGHT.into()),
+        )
+    }
+}
+
+/// Collects the changed byte span of consecutive rows, marking rows with the same span as one
+/// region.
+#[derive(Default)]
+struct RowSpans {
+    pending: Option<DirtyRegion>,
+}
+
+impl RowSpans {
+    /// Records the changed `columns` of `row`, rows must be pushed in increasing order.
+    fn push<D: DirtyTracker>(&mut self, dirty: &mut D, row: u8, columns: Option<(u8, u8)>) {
+        match (&mut self.pending, columns) {
+            (Some(region), Some(columns))
+                if region.columns == columns && region.rows.1 + 1 == row =>
+            {
+                region.rows.1 = row;
+            }
+            (_, Some(columns)) => {
+                self.finish(dirty);
+                self.pending = Some(DirtyRegion {
+                    columns,
+                    rows: (row, row),
+                });
+            }
+            (_, None) => {}
+        }
+    }
+
+    /// Marks the pending region.
+    fn finish<D: DirtyTracker>(&mut self, dirty: &mut D) {
+        if let Some(region) = self.pending.take() {
+            dirty.mark_region(region);
+        }
+    }
+}
+
+/// Mask of the nibbles of byte `column` inside the pixel span `x0..=x1`.
+#[inline]
+fn span_mask(column: usize, x0: usize, x1: usize) -> u8 {
+    let mut mask = 0xFF;
+    if column == x0 / 2 && x0 % 2 == 1 {
+        mask &= 0x0F;
+    }
+    if column == x1 / 2 && x1 % 2 != 1 {
+        mask &= 0xF0;
+    }
+    mask
+}
+
+/// Widens the byte span `span` to cover `column`.
+#[inline]
+fn widen(span: Option<(u8, u8)>, column: u8) -> (u8, u8) {
+    match span {
+        Some((first, last)) => (first.min(column), last.max(column)),
+        None => (column, column),
+    }
+}
+
+impl<SIZE: DisplaySize, D: DirtyTracker, B> BoundingBox for FrameBuffer<SIZE, D, B> {
+    fn update_box(&mut self, x: u16, y: u8) {
+        self.dirty.mark((x / 2) as u8, y);
+    }
+}
+
+#[inline]
+fn update_upper_nibble(input: u8, color: u8) -> u8 {
+    ((color << 4) & 0xF0) | (input & 0x0F)
+}
+
+#[inline]
+fn update_lower_nibble(input: u8, color: u8) -> u8 {
+    color & 0x0F | (input & 0xF0)
+}
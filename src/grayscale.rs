@@ -0,0 +1,162 @@
+//! Gray scale tables
+//!
+//! The SSD1322 maps the 16 gray levels of the GDDRAM to pulse widths in DCLKs. GS0 is always
+//! off; GS1 to GS15 come from either the built-in linear table (Select Default Linear Gray Scale
+//! Table, `0xB9`) or a custom table (Set Gray Scale Table, `0xB8`).
+
+/// Maximum pulse width of a gray level in DCLKs.
+pub const MAX_PULSE_WIDTH: u8 = 180;
+
+/// Error returned for a gray scale table that violates the datasheet rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrayScaleError {
+    /// The entry at this index (0 for GS1) is not greater than the previous one.
+    NotIncreasing(usize),
+    /// The entry at this index (0 for GS1) is greater than [`MAX_PULSE_WIDTH`].
+    OutOfRange(usize),
+    /// The gamma value is not a positive finite number.
+    InvalidGamma,
+}
+
+/// Pulse widths of the gray levels GS1 to GS15.
+///
+/// The datasheet requires `GS1 < GS2 < ... < GS15 <= 180`, which is checked on construction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GrayScaleTable([u8; 15]);
+
+impl GrayScaleTable {
+    /// Linear table spanning the full pulse width range.
+    pub const LINEAR: Self = Self([
+        12, 24, 36, 48, 60, 72, 84, 96, 108, 120, 132, 144, 156, 168, 180,
+    ]);
+
+    /// Gamma 1.8 curve.
+    pub const GAMMA_1_8: Self = Self([
+        1, 5, 10, 17, 25, 35, 46, 58, 72, 87, 103, 120, 139, 159, 180,
+    ]);
+
+    /// Gamma 2.2 curve, close to sRGB.
+    pub const GAMMA_2_2: Self = Self([0, 2, 5, 10, 16, 24, 34, 45, 59, 74, 91, 110, 131, 155, 180]);
+
+    /// Gamma 2.4 curve.
+    pub const GAMMA_2_4: Self = Self([0, 1, 4, 8, 13, 20, 29, 40, 53, 68, 86, 105, 128, 153, 180]);
+
+    /// Perceptually uniform steps following the CIE 1976 lightness curve.
+    pub const PERCEPTUAL: Self = Self([1, 3, 5, 9, 14, 20, 28, 38, 51, 65, 82, 102, 125, 151, 180]);
+
+    /// Creates a table from the pulse widths of GS1 to GS15.
+    pub fn new(entries: [u8; 15]) -> Result<Self, GrayScaleError> {
+        for (i, &entry) in entries.iter().enumerate() {
+            if entry > MAX_PULSE_WIDTH {
+                return Err(GrayScaleError::OutOfRange(i));
+            }
+            if i > 0 && entry <= entries[i - 1] {
+                return Err(GrayScaleError::NotIncreasing(i));
+            }
+        }
+
+        Ok(Self(entries))
+    }
+
+    /// Computes a table following `level ^ gamma` over the full pulse width range.
+    ///
+    /// Entries that would collide after rounding are spread apart to keep the table strictly
+    /// increasing.
+    pub fn from_gamma(gamma: f32) -> Result<Self, GrayScaleError> {
+        if !gamma.is_finite() || gamma <= 0.0 {
+            return Err(GrayScaleError::InvalidGamma);
+        }
+
+        let mut entries = [0u8; 15];
+        for (i, entry) in entries.iter_mut().enumerate() {
+            let level = (i + 1) as f32 / 15.0;
+            let width = libm::powf(level, gamma) * f32::from(MAX_PULSE_WIDTH) + 0.5;
+            *entry = width.min(f32::from(MAX_PULSE_WIDTH)) as u8;
+        }
+
+        // Push colliding low entries up, then pull colliding high entries down.
+        for i in 1..entries.len() {
+            if entries[i] <= entries[i - 1] {
+                entries[i] = entries[i - 1] + 1;
+            }
+        }
+        entries[14] = MAX_PULSE_WIDTH;
+        for i in (0..entries.len() - 1).rev() {
+            if entries[i] >= entries[i + 1] {
+                entries[i] = entries[i + 1] - 1;
+            }
+        }
+
+        Self::new(entries)
+    }
+
+    /// Returns the pulse widths of GS1 to GS15.
+    pub fn entries(&self) -> &[u8; 15] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_valid() {
+        for table in [
+            GrayScaleTable::LINEAR,
+            GrayScaleTable::GAMMA_1_8,
+            GrayScaleTable::GAMMA_2_2,
+            GrayScaleTable::GAMMA_2_4,
+            GrayScaleTable::PERCEPTUAL,
+        ] {
+            assert_eq!(GrayScaleTable::new(*table.entries()), Ok(table));
+        }
+    }
+
+    #[test]
+    fn invalid_tables_are_rejected() {
+        let mut entries = *GrayScaleTable::LINEAR.entries();
+        entries[14] = 181;
+        assert_eq!(
+            GrayScaleTable::new(entries),
+            Err(GrayScaleError::OutOfRange(14))
+        );
+
+        let mut entries = *GrayScaleTable::LINEAR.entries();
+        entries[3] = entries[2];
+        assert_eq!(
+            GrayScaleTable::new(entries),
+            Err(GrayScaleError::NotIncreasing(3))
+        );
+
+        assert_eq!(
+            GrayScaleTable::from_gamma(0.0),
+            Err(GrayScaleError::InvalidGamma)
+        );
+        assert_eq!(
+            GrayScaleTable::from_gamma(f32::NAN),
+            Err(GrayScaleError::InvalidGamma)
+        );
+    }
+
+    #[test]
+    fn from_gamma_matches_presets() {
+        for (gamma, preset) in [
+            (1.0, GrayScaleTable::LINEAR),
+            (1.8, GrayScaleTable::GAMMA_1_8),
+            (2.2, GrayScaleTable::GAMMA_2_2),
+            (2.4, GrayScaleTable::GAMMA_2_4),
+        ] {
+            let table = GrayScaleTable::from_gamma(gamma).unwrap();
+            for (computed, expected) in table.entries().iter().zip(preset.entries()) {
+                assert_eq!(computed, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn from_gamma_handles_extreme_values() {
+        assert!(GrayScaleTable::from_gamma(0.01).is_ok());
+        assert!(GrayScaleTable::from_gamma(10.0).is_ok());
+    }
+}
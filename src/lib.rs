@@ -1,4 +1,4 @@
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(missing_docs)]
 #![deny(trivial_casts)]
 #![deny(trivial_numeric_casts)]
@@ -10,5 +10,26 @@
 //! Builder example
 extern crate embedded_hal as hal;
 
-mod command;
+#[cfg(feature = "async")]
+pub mod async_display;
+pub mod brightness;
+pub mod burnin;
+pub mod command;
+pub mod config;
+pub mod direct;
+pub mod dirty;
 pub mod display;
+pub mod error;
+mod framebuffer;
+pub mod grayscale;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod mode;
+pub mod power;
+pub mod rotation;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+pub mod size;
+#[cfg(feature = "std")]
+pub mod snapshot;
+mod window;
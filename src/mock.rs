@@ -0,0 +1,403 @@
+//! Recording display interface for tests
+//!
+//! [`MockInterface`] records every command and data transfer sent by the driver, and emulates
+//! the parts of the SSD1322 that decide where pixel data lands: the column and row address
+//! window, [`Command::WriteRAM`](crate::command::Command::WriteRAM) and
+//! [`Command::ReadRAM`](crate::command::Command::ReadRAM), the remap and the display start line
+//! and offset. Tests can assert the exact bytes sent by `init` and `flush`, and
+//! compare the emulated panel with the framebuffer:
+//!
+//! ```
+//! use ssd1322_di::{display::Ssd1322, mock::MockInterface, size::DisplaySize256x64};
+//!
+//! let mut disp = Ssd1322::new(MockInterface::new());
+//! disp.init().unwrap();
+//! disp.flush_all().unwrap();
+//!
+//! let mock = disp.interface();
+//! assert_eq!(mock.panel::<DisplaySize256x64>(), disp.snapshot());
+//! ```
+#[cfg(feature = "async")]
+use display_interface::AsyncWriteOnlyDataCommand;
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+
+use crate::direct::ReadData;
+use crate::grayscale::GrayScaleTable;
+use crate::mode::DisplayMode;
+use crate::size::{DisplaySize, GDDRAM_COLUMNS, GDDRAM_ROWS, PIXELS_PER_COLUMN};
+use crate::snapshot::Snapshot;
+
+/// Opcodes interpreted by the emulation.
+const SET_COLUMN_ADDRESS: u8 = 0x15;
+const WRITE_RAM: u8 = 0x5C;
+const READ_RAM: u8 = 0x5D;
+const SET_ROW_ADDRESS: u8 = 0x75;
+const SET_REMAP_FORMAT: u8 = 0xA0;
+const SET_START_LINE: u8 = 0xA1;
+const SET_DISPLAY_OFFSET: u8 = 0xA2;
+const ALL_PIXELS_OFF: u8 = 0xA4;
+const ALL_PIXELS_ON: u8 = 0xA5;
+const NORMAL_DISPLAY_MODE: u8 = 0xA6;
+const INVERSE_DISPLAY_MODE: u8 = 0xA7;
+const DISPLAY_OFF: u8 = 0xAE;
+const DISPLAY_ON: u8 = 0xAF;
+const SET_GRAY_SCALE_TABLE: u8 = 0xB8;
+const SET_LINEAR_GRAY_SCALE_TABLE: u8 = 0xB9;
+const SET_CONTRAST_CURRENT: u8 = 0xC1;
+const SET_MASTER_CURRENT: u8 = 0xC7;
+
+/// Bytes in one GDDRAM row, a column address covers 2 bytes.
+const ROW_BYTES: usize = GDDRAM_COLUMNS as usize * 2;
+
+/// One call to the display interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transfer {
+    /// Bytes sent with `send_commands`.
+    Command(Vec<u8>),
+    /// Bytes sent with `send_data`.
+    Data(Vec<u8>),
+    /// Bytes returned by `read_data`.
+    Read(Vec<u8>),
+}
+
+/// Display interface recording the transfers and emulating the SSD1322 GDDRAM and display
+/// state.
+#[derive(Debug, Clone)]
+pub struct MockInterface {
+    transfers: Vec<Transfer>,
+    gddram: Vec<u8>,
+    /// Last command byte and the data bytes sent after it.
+    command: Option<(u8, Vec<u8>)>,
+    columns: (u8, u8),
+    rows: (u8, u8),
+    /// Column address, row address and byte within the column address of the next RAM access.
+    address: (u8, u8, u8),
+    /// Whether the next read returns the dummy byte following Read RAM.
+    dummy_read: bool,
+    remap: u8,
+    start_line: u8,
+    display_offset: u8,
+    display_on: bool,
+    display_mode: DisplayMode,
+    pulse_widths: [u8; 15],
+    master_current: u8,
+    contrast_current: u8,
+}
+
+impl Default for MockInterface {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockInterface {
+    /// Creates a mock with a zeroed GDDRAM and the reset state of the controller.
+    pub fn new() -> Self {
+        Self {
+            transfers: Vec::new(),
+            gddram: vec![0; ROW_BYTES * usize::from(GDDRAM_ROWS)],
+            command: None,
+            columns: (0, GDDRAM_COLUMNS - 1),
+            rows: (0, GDDRAM_ROWS - 1),
+            address: (0, 0, 0),
+            dummy_read: false,
+            remap: 0,
+            start_line: 0,
+            display_offset: 0,
+            display_on: false,
+            display_mode: DisplayMode::Normal,
+            pulse_widths: *GrayScaleTable::LINEAR.entries(),
+            master_current: 0x0F,
+            contrast_current: 0x7F,
+        }
+    }
+
+    /// Transfers recorded since the mock was created or last cleared.
+    pub fn transfers(&self) -> &[Transfer] {
+        &self.transfers
+    }
+
+    /// Recorded transfers grouped as commands, each opcode with the data bytes sent after it.
+    pub fn commands(&self) -> Vec<(u8, Vec<u8>)> {
+        let mut commands: Vec<(u8, Vec<u8>)> = Vec::new();
+        for transfer in &self.transfers {
+            match transfer {
+                Transfer::Command(bytes) => {
+                    commands.extend(bytes.iter().map(|&opcode| (opcode, Vec::new())))
+                }
+                Transfer::Data(bytes) => {
+                    if let Some((_, data)) = commands.last_mut() {
+                        data.extend_from_slice(bytes);
+                    }
+                }
+                Transfer::Read(_) => {}
+            }
+        }
+        commands
+    }
+
+    /// Forgets the recorded transfers, keeping the emulated controller state.
+    pub fn clear_transfers(&mut self) {
+        self.transfers.clear();
+    }
+
+    /// Emulated GDDRAM, 128 rows of 240 bytes in column address order.
+    pub fn gddram(&self) -> &[u8] {
+        &self.gddram
+    }
+
+    /// Whether the display is on (sleep mode off).
+    pub fn is_display_on(&self) -> bool {
+        self.display_on
+    }
+
+    /// Display mode selected by the last display mode command.
+    pub fn display_mode(&self) -> DisplayMode {
+        self.display_mode
+    }
+
+    /// Pulse widths of the gray levels GS1 to GS15 in use.
+    pub fn pulse_widths(&self) -> [u8; 15] {
+        self.pulse_widths
+    }
+
+    /// Master and contrast currents.
+    pub fn currents(&self) -> (u8, u8) {
+        (self.master_current, self.contrast_current)
+    }
+
+    /// GDDRAM contents shown on a panel of geometry `SIZE`, before the display mode, gray scale
+    /// table and currents are applied.
+    ///
+    /// The panel is assumed to be wired like the modules supported by the driver, so the default
+    /// configuration shows the framebuffer upright: the nibble remap puts the upper nibble of
+    /// each byte on the left, and the COM scan remap puts the start line on the top row.
+    pub fn panel<SIZE: DisplaySize>(&self) -> Snapshot {
+        let pixels = (0..SIZE::HEIGHT)
+            .flat_map(|y| (0..SIZE::WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| self.panel_pixel::<SIZE>(x, y))
+            .collect();
+        Snapshot::new(SIZE::WIDTH.into(), SIZE::HEIGHT.into(), pixels)
+            .expect("GDDRAM levels are 4 bit")
+    }
+
+    /// Gray level of the pixel at `x`, `y` of a panel of geometry `SIZE`.
+    fn panel_pixel<SIZE: DisplaySize>(&self, x: u16, y: u16) -> u8 {
+        let column_remap = self.remap & 0x02 != 0;
+        let nibble_remap = self.remap & 0x04 != 0;
+        let com_scan_reverse = self.remap & 0x10 != 0;
+
+        let line = if com_scan_reverse {
+            y
+        } else {
+            SIZE::HEIGHT - 1 - y
+        };
+        let rows = u16::from(GDDRAM_ROWS);
+        let line = (line + rows - u16::from(self.display_offset)) % rows;
+        let row = usize::from((u16::from(self.start_line) + line) % rows);
+
+        let segment = PIXELS_PER_COLUMN * u16::from(SIZE::COLUMN_OFFSET) + x;
+        let segment = if column_remap {
+            PIXELS_PER_COLUMN * u16::from(GDDRAM_COLUMNS) - 1 - segment
+        } else {
+            segment
+        };
+        let column = usize::from(segment / PIXELS_PER_COLUMN);
+        let nibble = usize::from(segment % PIXELS_PER_COLUMN);
+        let nibble = if nibble_remap { nibble } else { 3 - nibble };
+
+        let byte = self.gddram[row * ROW_BYTES + column * 2 + nibble / 2];
+        if nibble % 2 == 1 {
+            byte & 0x0F
+        } else {
+            byte >> 4
+        }
+    }
+
+    fn receive_commands(&mut self, bytes: Vec<u8>) {
+        for &opcode in &bytes {
+            self.command = Some((opcode, Vec::new()));
+            self.execute(opcode, &[]);
+        }
+        self.transfers.push(Transfer::Command(bytes));
+    }
+
+    fn receive_data(&mut self, bytes: Vec<u8>) {
+        match &mut self.command {
+            Some((WRITE_RAM, _)) => bytes.iter().for_each(|&byte| self.write_ram(byte)),
+            Some((opcode, data)) => {
+                data.extend_from_slice(&bytes);
+                let (opcode, data) = (*opcode, data.clone());
+                self.execute(opcode, &data);
+            }
+            None => {}
+        }
+        self.transfers.push(Transfer::Data(bytes));
+    }
+
+    /// Applies the commands changing the address window, the mapping to the panel or the light
+    /// output.
+    fn execute(&mut self, opcode: u8, data: &[u8]) {
+        match (opcode, data) {
+            (SET_COLUMN_ADDRESS, &[start, end]) => {
+                self.columns = (start, end);
+                self.address.0 = start;
+                self.address.2 = 0;
+            }
+            (SET_ROW_ADDRESS, &[start, end]) => {
+                self.rows = (start, end);
+                self.address.1 = start;
+                self.address.2 = 0;
+            }
+            (READ_RAM, &[]) => self.dummy_read = true,
+            (SET_REMAP_FORMAT, &[a, _]) => self.remap = a,
+            (SET_START_LINE, &[line]) => self.start_line = line % GDDRAM_ROWS,
+            (SET_DISPLAY_OFFSET, &[offset]) => self.display_offset = offset % GDDRAM_ROWS,
+            (ALL_PIXELS_OFF, &[]) => self.display_mode = DisplayMode::AllOff,
+            (ALL_PIXELS_ON, &[]) => self.display_mode = DisplayMode::AllOn,
+            (NORMAL_DISPLAY_MODE, &[]) => self.display_mode = DisplayMode::Normal,
+            (INVERSE_DISPLAY_MODE, &[]) => self.display_mode = DisplayMode::Inverse,
+            (DISPLAY_OFF, &[]) => self.display_on = false,
+            (DISPLAY_ON, &[]) => self.display_on = true,
+            (SET_GRAY_SCALE_TABLE, entries) if entries.len() == 15 => {
+                self.pulse_widths.copy_from_slice(entries);
+            }
+            (SET_LINEAR_GRAY_SCALE_TABLE, &[]) => {
+                self.pulse_widths = *GrayScaleTable::LINEAR.entries()
+            }
+            (SET_CONTRAST_CURRENT, &[current]) => self.contrast_current = current,
+            (SET_MASTER_CURRENT, &[current]) => self.master_current = current & 0x0F,
+            _ => {}
+        }
+    }
+
+    /// Writes `byte` at the RAM address and advances it inside the address window.
+    fn write_ram(&mut self, byte: u8) {
+        if let Some(index) = self.ram_index() {
+            self.gddram[index] = byte;
+        }
+        self.advance();
+    }
+
+    /// Reads the byte at the RAM address and advances it inside the address window.
+    fn read_ram(&mut self) -> u8 {
+        if self.dummy_read {
+            self.dummy_read = false;
+            return 0;
+        }
+        let byte = self.ram_index().map_or(0, |index| self.gddram[index]);
+        self.advance();
+        byte
+    }
+
+    /// Index of the RAM address in the GDDRAM, `None` if outside.
+    fn ram_index(&self) -> Option<usize> {
+        let (column, row, half) = self.address;
+        (column < GDDRAM_COLUMNS && row < GDDRAM_ROWS)
+            .then(|| usize::from(row) * ROW_BYTES + usize::from(column) * 2 + usize::from(half))
+    }
+
+    /// Moves the RAM address to the next byte inside the address window.
+    fn advance(&mut self) {
+        let (column, row, half) = self.address;
+        if half == 0 {
+            self.address.2 = 1;
+            return;
+        }
+
+        let vertical = self.remap & 0x01 != 0;
+        let (mut column, mut row) = (column, row);
+        if vertical {
+            row = step(row, self.rows);
+            if row == self.rows.0 {
+                column = step(column, self.columns);
+            }
+        } else {
+            column = step(column, self.columns);
+            if column == self.columns.0 {
+                row = step(row, self.rows);
+            }
+        }
+        self.address = (column, row, 0);
+    }
+}
+
+/// Next address after `address` inside `range`, wrapping to its start.
+fn step(address: u8, range: (u8, u8)) -> u8 {
+    if address >= range.1 {
+        range.0
+    } else {
+        address + 1
+    }
+}
+
+/// Collects the bytes of `format`.
+fn bytes(format: DataFormat<'_>) -> Result<Vec<u8>, DisplayError> {
+    match format {
+        DataFormat::U8(bytes) => Ok(bytes.to_vec()),
+        DataFormat::U8Iter(iter) => Ok(iter.collect()),
+        _ => Err(DisplayError::DataFormatNotImplemented),
+    }
+}
+
+impl WriteOnlyDataCommand for MockInterface {
+    fn send_commands(&mut self, cmds: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.receive_commands(bytes(cmds)?);
+        Ok(())
+    }
+
+    fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.receive_data(bytes(buf)?);
+        Ok(())
+    }
+}
+
+/// Reads the GDDRAM after [`Command::ReadRAM`](crate::command::Command::ReadRAM), starting with
+/// a dummy byte like the controller.
+impl ReadData for MockInterface {
+    fn read_data(&mut self, buf: &mut [u8]) -> Result<(), DisplayError> {
+        if !matches!(self.command, Some((READ_RAM, _))) {
+            return Err(DisplayError::InvalidFormatError);
+        }
+        buf.iter_mut().for_each(|byte| *byte = self.read_ram());
+        self.transfers.push(Transfer::Read(buf.to_vec()));
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncWriteOnlyDataCommand for MockInterface {
+    async fn send_commands(&mut self, cmds: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.receive_commands(bytes(cmds)?);
+        Ok(())
+    }
+
+    async fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.receive_data(bytes(buf)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn ram_writes_wrap_inside_the_window() {
+        let mut mock = MockInterface::new();
+        Command::SetColumnAddress(2, 3).send(&mut mock).unwrap();
+        Command::SetRowAddress(5, 6).send(&mut mock).unwrap();
+        Command::WriteRAM.send(&mut mock).unwrap();
+        WriteOnlyDataCommand::send_data(&mut mock, DataFormat::U8(&[1, 2, 3, 4, 5, 6, 7, 8, 9]))
+            .unwrap();
+
+        let row = |r: usize| &mock.gddram()[r * ROW_BYTES + 4..r * ROW_BYTES + 8];
+        assert_eq!(row(5), [9, 2, 3, 4]);
+        assert_eq!(row(6), [5, 6, 7, 8]);
+        assert_eq!(
+            mock.commands()[..2],
+            [(0x15, vec![2, 3]), (0x75, vec![5, 6])]
+        );
+    }
+}
@@ -0,0 +1,29 @@
+//! Display modes
+
+use crate::command::Command;
+
+/// How the display shows the GDDRAM content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayMode {
+    /// Shows the GDDRAM content.
+    #[default]
+    Normal,
+    /// Shows the GDDRAM content with inverted gray levels.
+    Inverse,
+    /// Lights every pixel at the maximum gray level, ignoring the GDDRAM.
+    AllOn,
+    /// Turns every pixel off, ignoring the GDDRAM.
+    AllOff,
+}
+
+impl DisplayMode {
+    /// Command selecting this mode.
+    pub fn command(self) -> Command {
+        match self {
+            DisplayMode::Normal => Command::NormalDisplayMode,
+            DisplayMode::Inverse => Command::InverseDisplayMode,
+            DisplayMode::AllOn => Command::AllPixelsOn,
+            DisplayMode::AllOff => Command::AllPixelsOff,
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! Power states
+
+/// Power state of the display, from brightest to lowest power.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PowerState {
+    /// Display on with the configured master current.
+    On,
+    /// Display on with the dimmed master current of the configuration.
+    Dim,
+    /// Display off (sleep mode), the GDDRAM keeps its content.
+    #[default]
+    Sleep,
+    /// Display off and internal VDD regulator off. The GDDRAM content is lost and rewritten from
+    /// the framebuffer on wake.
+    Off,
+}
+
+impl PowerState {
+    /// Returns true if the panel is lit in this state.
+    pub fn is_display_on(self) -> bool {
+        matches!(self, PowerState::On | PowerState::Dim)
+    }
+}
@@ -0,0 +1,35 @@
+//! Display rotation
+
+/// Rotation of the displayed image, clockwise.
+///
+/// 180° is done with the hardware remap of the controller. 90° and 270° swap the axes of the
+/// framebuffer in software and use the hardware remap for the remaining flip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayRotation {
+    /// No rotation.
+    #[default]
+    Rotate0,
+    /// Rotate by 90 degrees clockwise.
+    Rotate90,
+    /// Rotate by 180 degrees.
+    Rotate180,
+    /// Rotate by 270 degrees clockwise.
+    Rotate270,
+}
+
+impl DisplayRotation {
+    /// Returns true if the rotation swaps width and height.
+    pub fn is_transposed(self) -> bool {
+        matches!(self, DisplayRotation::Rotate90 | DisplayRotation::Rotate270)
+    }
+
+    /// Returns the hardware `(horizontal, vertical)` flips needed for this rotation.
+    pub(crate) fn flips(self) -> (bool, bool) {
+        match self {
+            DisplayRotation::Rotate0 => (false, false),
+            DisplayRotation::Rotate90 => (true, false),
+            DisplayRotation::Rotate180 => (true, true),
+            DisplayRotation::Rotate270 => (false, true),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Desktop simulator
+//!
+//! A [`SimulatedSsd1322`], created with [`Ssd1322::simulated`], is the regular driver connected to
+//! a [`MockInterface`], so drawing, dirty tracking and flushing run exactly as on the target.
+//! [`render`] turns the emulated panel into an [`embedded_graphics_simulator::SimulatorDisplay`],
+//! which can be saved as an image or, with the `with-sdl` feature of `embedded-graphics-simulator`,
+//! shown in a window:
+//!
+//! ```
+//! use embedded_graphics::{pixelcolor::Gray4, prelude::*, primitives::*};
+//! use embedded_graphics_simulator::OutputSettings;
+//! use ssd1322_di::display::Ssd1322;
+//!
+//! let mut disp = Ssd1322::simulated();
+//! disp.init().unwrap();
+//! Circle::new(Point::new(100, 4), 56)
+//!     .into_styled(PrimitiveStyle::with_fill(Gray4::WHITE))
+//!     .draw(&mut disp)
+//!     .unwrap();
+//! disp.flush().unwrap();
+//!
+//! let image = disp.render().to_grayscale_output_image(&OutputSettings::default());
+//! # let _ = image;
+//! ```
+//!
+//! Only what was flushed is rendered. Each pixel is lit according to its gray level, the display
+//! mode, the gray scale table and the master and contrast currents, and the light output is
+//! mapped to a gray value along the CIE 1976 lightness curve.
+use crate::dirty::{DirtyRects, DirtyTracker};
+use crate::display::Ssd1322;
+use crate::grayscale::MAX_PULSE_WIDTH;
+use crate::mock::MockInterface;
+use crate::mode::DisplayMode;
+use crate::size::{DisplaySize, DisplaySize256x64};
+use embedded_graphics::{pixelcolor::Gray8, prelude::*};
+use embedded_graphics_simulator::SimulatorDisplay;
+
+/// Driver connected to an emulated panel.
+pub type SimulatedSsd1322<SIZE = DisplaySize256x64, D = DirtyRects<1>> =
+    Ssd1322<MockInterface, SIZE, D>;
+
+impl Ssd1322<MockInterface> {
+    /// Creates a driver connected to a new emulated 256x64 panel. Other panels are simulated
+    /// with [`Ssd1322::with_size`] and a [`MockInterface`].
+    pub fn simulated() -> Self {
+        Self::new(MockInterface::new())
+    }
+}
+
+impl<SIZE: DisplaySize, D: DirtyTracker, B: AsMut<[u8]> + AsRef<[u8]>>
+    Ssd1322<MockInterface, SIZE, D, B>
+{
+    /// Renders the emulated panel.
+    pub fn render(&self) -> SimulatorDisplay<Gray8> {
+        render::<SIZE>(self.interface())
+    }
+}
+
+/// Renders the panel of geometry `SIZE` emulated by `mock`.
+pub fn render<SIZE: DisplaySize>(mock: &MockInterface) -> SimulatorDisplay<Gray8> {
+    let panel = mock.panel::<SIZE>();
+    let mut display = SimulatorDisplay::new(Size::new(panel.width(), panel.height()));
+
+    let pulse_widths = mock.pulse_widths();
+    let (master, contrast) = mock.currents();
+    // Fraction of the highest current
+    let current = (f32::from(master) + 1.0) * (f32::from(contrast) + 1.0) / 4096.0;
+    let pixels = (0..panel.height())
+        .flat_map(|y| (0..panel.width()).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let level = match mock.display_mode() {
+                _ if !mock.is_display_on() => 0,
+                DisplayMode::Normal => panel.pixel(x, y).unwrap_or(0),
+                DisplayMode::Inverse => 15 - panel.pixel(x, y).unwrap_or(0),
+                DisplayMode::AllOn => 15,
+                DisplayMode::AllOff => 0,
+            };
+            let width = match level {
+                0 => 0,
+                level => pulse_widths[usize::from(level) - 1],
+            };
+            let output = f32::from(width) / f32::from(MAX_PULSE_WIDTH) * current;
+            let point = Point::new(x as i32, y as i32);
+            Pixel(point, Gray8::new(gray(output)))
+        });
+    display
+        .draw_iter(pixels)
+        .expect("simulator display is infallible");
+
+    display
+}
+
+/// Gray value of the relative light output `output` on the CIE 1976 lightness curve.
+fn gray(output: f32) -> u8 {
+    let output = output.clamp(0.0, 1.0);
+    let lightness = if output > 0.008856 {
+        116.0 * libm::cbrtf(output) - 16.0
+    } else {
+        903.3 * output
+    };
+    (lightness * 2.55 + 0.5) as u8
+}
@@ -0,0 +1,158 @@
+//! Panel geometry
+//!
+//! The SSD1322 has a 480x128 pixel GDDRAM, but most modules only wire up a window of it. A
+//! [`DisplaySize`] describes that window so the driver can derive its column/row addresses,
+//! multiplex ratio and framebuffer size from it.
+//!
+//! The presets cover the modules named on them and the full GDDRAM. Other modules implement
+//! [`DisplaySize`] with the size and first column address given in their datasheet:
+//!
+//! ```
+//! # use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+//! # struct Bus;
+//! # impl WriteOnlyDataCommand for Bus {
+//! #     fn send_commands(&mut self, _: DataFormat<'_>) -> Result<(), DisplayError> { Ok(()) }
+//! #     fn send_data(&mut self, _: DataFormat<'_>) -> Result<(), DisplayError> { Ok(()) }
+//! # }
+//! use ssd1322_di::{display::Ssd1322, size::DisplaySize};
+//!
+//! /// 128x64 module wired to the column addresses 0x1C..=0x3B.
+//! struct Module128x64;
+//!
+//! impl DisplaySize for Module128x64 {
+//!     const WIDTH: u16 = 128;
+//!     const HEIGHT: u16 = 64;
+//!     const COLUMN_OFFSET: u8 = 0x1C;
+//!     type Buffer = [u8; 128 * 64 / 2];
+//!
+//!     fn new_buffer() -> Self::Buffer {
+//!         [0; 128 * 64 / 2]
+//!     }
+//! }
+//!
+//! let disp = Ssd1322::with_size(Bus, Module128x64);
+//! ```
+use core::marker::PhantomData;
+
+/// Number of pixels covered by one SSD1322 column address.
+pub const PIXELS_PER_COLUMN: u16 = 4;
+
+/// Number of column addresses of the GDDRAM.
+pub const GDDRAM_COLUMNS: u8 = 120;
+
+/// Number of rows of the GDDRAM.
+pub const GDDRAM_ROWS: u8 = 128;
+
+/// Describes the geometry of the panel attached to the SSD1322.
+///
+/// The panel must fit the GDDRAM, a geometry that doesn't fails to build:
+///
+/// ```compile_fail
+/// # use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+/// # struct Bus;
+/// # impl WriteOnlyDataCommand for Bus {
+/// #     fn send_commands(&mut self, _: DataFormat<'_>) -> Result<(), DisplayError> { Ok(()) }
+/// #     fn send_data(&mut self, _: DataFormat<'_>) -> Result<(), DisplayError> { Ok(()) }
+/// # }
+/// use ssd1322_di::{display::Ssd1322, size::DisplaySize};
+///
+/// /// 256 pixels starting 100 column addresses in, past the 120 of the GDDRAM.
+/// struct OffTheEdge;
+///
+/// impl DisplaySize for OffTheEdge {
+///     const WIDTH: u16 = 256;
+///     const HEIGHT: u16 = 64;
+///     const COLUMN_OFFSET: u8 = 100;
+///     type Buffer = [u8; 256 * 64 / 2];
+///
+///     fn new_buffer() -> Self::Buffer {
+///         [0; 256 * 64 / 2]
+///     }
+/// }
+///
+/// let disp = Ssd1322::with_size(Bus, OffTheEdge);
+/// ```
+pub trait DisplaySize {
+    /// Width of the panel in pixels. Must be a multiple of 4 and at most 480.
+    const WIDTH: u16;
+
+    /// Height of the panel in pixels. Must be between 16 and 128.
+    const HEIGHT: u16;
+
+    /// First column address (in units of 4 pixels) of the panel inside the GDDRAM, without the
+    /// column address remap. The panel must end inside the 120 column addresses.
+    const COLUMN_OFFSET: u8;
+
+    /// Multiplex ratio sent to the controller, i.e. the number of driven rows minus one.
+    const MUX_RATIO: u8 = (Self::HEIGHT - 1) as u8;
+
+    /// Framebuffer type holding `WIDTH * HEIGHT / 2` bytes (two pixels per byte). Creating a
+    /// driver panics if [`new_buffer`](Self::new_buffer) returns a different number of bytes.
+    type Buffer: AsMut<[u8]> + AsRef<[u8]>;
+
+    /// Creates a zeroed framebuffer.
+    fn new_buffer() -> Self::Buffer;
+
+    /// Column address range `(start, end)` of the panel inside the GDDRAM. The column address
+    /// remap mirrors the range when the panel is not centered in the GDDRAM.
+    fn column_range(column_remap: bool) -> (u8, u8) {
+        crate::window::column_range(Self::WIDTH, Self::COLUMN_OFFSET, column_remap)
+    }
+}
+
+/// Compile-time check of the invariants of a [`DisplaySize`].
+pub(crate) struct Geometry<SIZE>(PhantomData<SIZE>);
+
+impl<SIZE: DisplaySize> Geometry<SIZE> {
+    /// Fails the build when `SIZE` doesn't fit the GDDRAM, evaluated by the drivers.
+    pub(crate) const CHECK: () = {
+        assert!(
+            SIZE::WIDTH > 0 && SIZE::WIDTH % PIXELS_PER_COLUMN == 0,
+            "panel width must be a non-zero multiple of 4"
+        );
+        assert!(
+            SIZE::COLUMN_OFFSET as u16 + SIZE::WIDTH / PIXELS_PER_COLUMN <= GDDRAM_COLUMNS as u16,
+            "panel columns must end inside the GDDRAM"
+        );
+        assert!(
+            SIZE::HEIGHT >= 16 && SIZE::HEIGHT <= GDDRAM_ROWS as u16,
+            "panel height must be between 16 and 128"
+        );
+    };
+}
+
+macro_rules! display_size {
+    ($(#[$doc:meta])* $name:ident, $width:expr, $height:expr, $offset:expr) => {
+        $(#[$doc])*
+        #[derive(Debug, Copy, Clone)]
+        pub struct $name;
+
+        impl DisplaySize for $name {
+            const WIDTH: u16 = $width;
+            const HEIGHT: u16 = $height;
+            const COLUMN_OFFSET: u8 = $offset;
+            type Buffer = [u8; $width * $height / 2];
+
+            fn new_buffer() -> Self::Buffer {
+                [0; $width * $height / 2]
+            }
+        }
+    };
+}
+
+display_size!(
+    /// 256x64 panel, e.g. Newhaven NHD-3.12-25664UCB2, Winstar WEO025664 or ER-OLEDM032-1.
+    DisplaySize256x64,
+    256,
+    64,
+    0x1C
+);
+
+display_size!(
+    /// The full 480x128 GDDRAM of the controller, 120 column addresses by 128 rows as given in
+    /// the SSD1322 datasheet.
+    DisplaySize480x128,
+    480,
+    128,
+    0x00
+);
@@ -0,0 +1,232 @@
+//! Host-side framebuffer snapshots
+//!
+//! A [`Snapshot`] is a copy of the image drawn into the driver, one gray level `0..=15` per
+//! pixel in the orientation seen by the application. It can be written as a PGM image, or as a
+//! PNG image with the `png` feature, and compared against a golden PGM image with
+//! [`Snapshot::assert_matches`] so rendering regressions show up in host tests.
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Environment variable that makes [`Snapshot::assert_matches`] rewrite the golden images.
+pub const UPDATE_ENV: &str = "UPDATE_SNAPSHOTS";
+
+/// Gray levels of an image, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Snapshot {
+    /// Creates a snapshot from `width * height` gray levels, row by row.
+    ///
+    /// Returns `None` if the number of pixels doesn't match or a level is above 15.
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Option<Self> {
+        let valid = pixels.len() == (width * height) as usize && pixels.iter().all(|&p| p <= 15);
+        valid.then_some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Gray level of the pixel at `x`, `y`, `None` if outside the image.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.pixels[(y * self.width + x) as usize])
+    }
+
+    /// Writes the snapshot as a binary PGM image with a maximum gray level of 15.
+    pub fn write_pgm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "P5\n{} {}\n15\n", self.width, self.height)?;
+        writer.write_all(&self.pixels)
+    }
+
+    /// Returns the snapshot as a binary PGM image.
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut pgm = Vec::new();
+        self.write_pgm(&mut pgm)
+            .expect("writing to a Vec never fails");
+        pgm
+    }
+
+    /// Reads a binary PGM image written by [`write_pgm`](Self::write_pgm).
+    pub fn from_pgm(data: &[u8]) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        // Magic number, width, height and maximum gray level separated by single whitespace
+        let mut fields = [0u32; 3];
+        let mut rest = data
+            .strip_prefix(b"P5")
+            .ok_or_else(|| invalid("not a binary PGM image"))?;
+        for field in &mut fields {
+            let digits = rest
+                .iter()
+                .skip(1)
+                .take_while(|byte| byte.is_ascii_digit())
+                .count();
+            let text = std::str::from_utf8(&rest[1..1 + digits]).unwrap_or_default();
+            *field = text.parse().map_err(|_| invalid("invalid PGM header"))?;
+            rest = &rest[1 + digits..];
+        }
+        let [width, height, max] = fields;
+        if max != 15 {
+            return Err(invalid("PGM maximum gray level is not 15"));
+        }
+
+        Self::new(width, height, rest.get(1..).unwrap_or_default().to_vec())
+            .ok_or_else(|| invalid("PGM pixel data doesn't match its header"))
+    }
+
+    /// Writes the snapshot as an 8 bit grayscale PNG image.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        // Scale 0..=15 to 0..=255
+        let pixels: Vec<u8> = self.pixels.iter().map(|&level| level * 17).collect();
+        writer.write_image_data(&pixels)
+    }
+
+    /// Compares the snapshot against the golden PGM image at `path`, panicking on a mismatch.
+    ///
+    /// The golden image is written instead only if the `UPDATE_SNAPSHOTS` environment variable
+    /// is set, a missing golden image panics. On a mismatch the snapshot is written next to it
+    /// with an `.actual.pgm` extension.
+    pub fn assert_matches<P: AsRef<Path>>(&self, path: P) {
+        self.check(path.as_ref(), std::env::var_os(UPDATE_ENV).is_some());
+    }
+
+    /// Compares against or, if `update` is set, writes the golden image at `path`.
+    fn check(&self, path: &Path, update: bool) {
+        if update {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).expect("failed to create the snapshot directory");
+            }
+            fs::write(path, self.to_pgm()).expect("failed to write the golden image");
+            return;
+        }
+
+        if !path.exists() {
+            panic!(
+                "golden image {} is missing, run with {}=1 to create it",
+                path.display(),
+                UPDATE_ENV
+            );
+        }
+        let golden = fs::read(path).expect("failed to read the golden image");
+        let golden = Snapshot::from_pgm(&golden).expect("invalid golden image");
+        if let Some(message) = self.mismatch(&golden) {
+            let actual = actual_path(path);
+            fs::write(&actual, self.to_pgm()).expect("failed to write the actual image");
+            panic!(
+                "snapshot {} doesn't match: {}, actual image written to {}",
+                path.display(),
+                message,
+                actual.display()
+            );
+        }
+    }
+
+    /// Describes the difference with `golden`, if any.
+    fn mismatch(&self, golden: &Snapshot) -> Option<String> {
+        if (self.width, self.height) != (golden.width, golden.height) {
+            return Some(format!(
+                "size {}x{} instead of {}x{}",
+                self.width, self.height, golden.width, golden.height
+            ));
+        }
+
+        let mut differences = self
+            .pixels
+            .iter()
+            .zip(&golden.pixels)
+            .enumerate()
+            .filter(|(_, (actual, expected))| actual != expected);
+        let (first, (actual, expected)) = differences.next()?;
+        Some(format!(
+            "{} pixels differ, first at ({}, {}) with level {} instead of {}",
+            differences.count() + 1,
+            first as u32 % self.width,
+            first as u32 / self.width,
+            actual,
+            expected
+        ))
+    }
+}
+
+/// Path of the image written for a failed comparison against `golden`.
+fn actual_path(golden: &Path) -> PathBuf {
+    golden.with_extension("actual.pgm")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient() -> Snapshot {
+        Snapshot::new(16, 2, (0..32).map(|i| i % 16).collect()).unwrap()
+    }
+
+    #[test]
+    fn pgm_round_trip() {
+        let snapshot = gradient();
+        let pgm = snapshot.to_pgm();
+        assert!(pgm.starts_with(b"P5\n16 2\n15\n"));
+        assert_eq!(Snapshot::from_pgm(&pgm).unwrap(), snapshot);
+
+        assert!(Snapshot::from_pgm(b"P5\n16 2\n255\n").is_err());
+        assert!(Snapshot::from_pgm(&pgm[..pgm.len() - 1]).is_err());
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_export_scales_levels() {
+        let mut png = Vec::new();
+        gradient().write_png(&mut png).unwrap();
+
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (16, 2));
+        assert_eq!(pixels[..3], [0, 17, 34]);
+        assert_eq!(pixels[31], 255);
+    }
+
+    #[test]
+    #[should_panic(expected = "is missing")]
+    fn missing_golden_image_fails() {
+        let path = std::env::temp_dir().join("ssd1322_di_missing_golden.pgm");
+        gradient().check(&path, false);
+    }
+
+    #[test]
+    fn mismatch_reports_first_pixel() {
+        let golden = gradient();
+        let mut pixels = golden.pixels.clone();
+        pixels[17] = 0;
+        let actual = Snapshot::new(16, 2, pixels).unwrap();
+
+        assert_eq!(golden.mismatch(&golden), None);
+        assert_eq!(
+            actual.mismatch(&golden).unwrap(),
+            "1 pixels differ, first at (1, 1) with level 0 instead of 1"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Address windows
+//!
+//! A column address of the SSD1322 covers [`PIXELS_PER_COLUMN`] pixels, i.e. 2 bytes of a
+//! framebuffer row, and a panel occupies a range of column addresses inside the GDDRAM that the
+//! column remap mirrors. Changes are tracked in bytes and drawn in pixels, so they are widened
+//! here to whole column addresses before being mapped to the column addresses of the panel.
+use crate::dirty::{DirtyRegion, WINDOW_OVERHEAD};
+use crate::size::{GDDRAM_COLUMNS, GDDRAM_ROWS, PIXELS_PER_COLUMN};
+
+/// Number of framebuffer bytes covered by one column address.
+pub(crate) const BYTES_PER_COLUMN: usize = PIXELS_PER_COLUMN as usize / 2;
+
+/// Part of the framebuffer that needs to be sent to the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Window {
+    /// First byte of each row, always at the start of a column address.
+    pub(crate) first_byte: usize,
+    /// Number of bytes in each row, always whole column addresses.
+    pub(crate) num_bytes: usize,
+    /// First and last row.
+    pub(crate) rows: (u8, u8),
+}
+
+impl Window {
+    /// Window covering the bytes `bytes.0..=bytes.1` of the rows `rows`, widened to whole column
+    /// addresses.
+    pub(crate) fn from_bytes(bytes: (usize, usize), rows: (u8, u8)) -> Self {
+        let first_column = bytes.0 / BYTES_PER_COLUMN;
+        let last_column = bytes.1 / BYTES_PER_COLUMN;
+        Window {
+            first_byte: first_column * BYTES_PER_COLUMN,
+            num_bytes: (last_column - first_column + 1) * BYTES_PER_COLUMN,
+            rows,
+        }
+    }
+
+    /// Window covering `region`, widened to whole column addresses.
+    pub(crate) fn from_region(region: DirtyRegion) -> Self {
+        let bytes = (region.columns.0.into(), region.columns.1.into());
+        Self::from_bytes(bytes, region.rows)
+    }
+
+    /// Window covering the pixels `pixels.0..=pixels.1` of the rows `rows`, widened to whole
+    /// column addresses.
+    pub(crate) fn from_pixels(pixels: (usize, usize), rows: (u8, u8)) -> Self {
+        Self::from_bytes((pixels.0 / 2, pixels.1 / 2), rows)
+    }
+
+    /// Approximate number of bytes needed to send the window.
+    pub(crate) fn cost(&self) -> usize {
+        self.num_bytes * (usize::from(self.rows.1 - self.rows.0) + 1) + WINDOW_OVERHEAD
+    }
+
+    /// Part of the window inside the rows `rows.0..=rows.1`, if any.
+    pub(crate) fn clip_rows(self, rows: (u8, u8)) -> Option<Self> {
+        let first = self.rows.0.max(rows.0);
+        let last = self.rows.1.min(rows.1);
+        (first <= last).then_some(Window {
+            rows: (first, last),
+            ..self
+        })
+    }
+
+    /// Splits the window where its GDDRAM rows wrap around when row 0 is at GDDRAM row `scroll`,
+    /// returning each part with the GDDRAM row of its first row.
+    pub(crate) fn segments(self, scroll: u8) -> impl Iterator<Item = (Window, u8)> {
+        let first_row = (self.rows.0 as u16 + scroll as u16) % GDDRAM_ROWS as u16;
+        // Last row before the GDDRAM row address wraps
+        let last_unwrapped = self.rows.0 as u16 + (GDDRAM_ROWS as u16 - first_row) - 1;
+
+        let segments = if last_unwrapped >= self.rows.1 as u16 {
+            [Some((self, first_row as u8)), None]
+        } else {
+            let last_unwrapped = last_unwrapped as u8;
+            [
+                Some((
+                    Window {
+                        rows: (self.rows.0, last_unwrapped),
+                        ..self
+                    },
+                    first_row as u8,
+                )),
+                Some((
+                    Window {
+                        rows: (last_unwrapped + 1, self.rows.1),
+                        ..self
+                    },
+                    0,
+                )),
+            ]
+        };
+        IntoIterator::into_iter(segments).flatten()
+    }
+
+    /// Column addresses of the window for a panel at the column addresses `column_range`, as
+    /// returned by [`column_range`].
+    pub(crate) fn columns(&self, column_range: (u8, u8)) -> (u8, u8) {
+        let first = self.first_byte / BYTES_PER_COLUMN;
+        let last = (self.first_byte + self.num_bytes) / BYTES_PER_COLUMN - 1;
+        debug_assert!(
+            last <= usize::from(column_range.1 - column_range.0),
+            "window crosses the panel edge"
+        );
+        (column_range.0 + first as u8, column_range.0 + last as u8)
+    }
+}
+
+/// Column address range `(start, end)` of a panel `width` pixels wide starting at column address
+/// `offset` of the GDDRAM. The column address remap mirrors the range when the panel is not
+/// centered in the GDDRAM.
+pub(crate) fn column_range(width: u16, offset: u8, column_remap: bool) -> (u8, u8) {
+    let columns = (width / PIXELS_PER_COLUMN) as u8;
+    let start = if column_remap {
+        GDDRAM_COLUMNS - offset - columns
+    } else {
+        offset
+    };
+    (start, start + columns - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Panel width in pixels, column offset and column remap of panels fitting the GDDRAM.
+    fn panel() -> impl Strategy<Value = (u16, u8, bool)> {
+        (1..=GDDRAM_COLUMNS).prop_flat_map(|columns| {
+            let width = u16::from(columns) * PIXELS_PER_COLUMN;
+            (Just(width), 0..=GDDRAM_COLUMNS - columns, any::<bool>())
+        })
+    }
+
+    /// A panel with a span of dirty pixels inside it.
+    fn dirty_span() -> impl Strategy<Value = ((u16, u8, bool), (usize, usize))> {
+        panel().prop_flat_map(|panel| {
+            let width = usize::from(panel.0);
+            let span = (0..width).prop_flat_map(move |x0| (Just(x0), x0..width));
+            (Just(panel), span)
+        })
+    }
+
+    #[test]
+    fn windows_are_mapped_to_the_panel_columns() {
+        // 256x64 module centered in the GDDRAM
+        let range = column_range(256, 0x1C, false);
+        assert_eq!(range, (0x1C, 0x5B));
+        assert_eq!(column_range(256, 0x1C, true), range);
+        // 128 pixels starting at the left edge of the GDDRAM, mirrored to the right edge
+        assert_eq!(column_range(128, 0, true), (88, 119));
+
+        let window = Window::from_region(DirtyRegion {
+            columns: (5, 6),
+            rows: (2, 3),
+        });
+        assert_eq!((window.first_byte, window.num_bytes), (4, 4));
+        assert_eq!(window.columns(range), (0x1E, 0x1F));
+    }
+
+    proptest! {
+        #[test]
+        fn column_range_fits_the_gddram((width, offset, remap) in panel()) {
+            let (start, end) = column_range(width, offset, remap);
+            prop_assert!(start <= end && end < GDDRAM_COLUMNS);
+            prop_assert_eq!(u16::from(end - start + 1) * PIXELS_PER_COLUMN, width);
+            if remap {
+                prop_assert_eq!(GDDRAM_COLUMNS - 1 - end, offset);
+            } else {
+                prop_assert_eq!(start, offset);
+            }
+        }
+
+        #[test]
+        fn windows_cover_dirty_pixels_inside_the_panel(
+            ((width, offset, remap), (x0, x1)) in dirty_span(),
+            rows in (0..128u8).prop_flat_map(|first| (Just(first), first..128)),
+        ) {
+            let row_bytes = usize::from(width) / 2;
+            let range = column_range(width, offset, remap);
+            let windows = [
+                Window::from_pixels((x0, x1), rows),
+                Window::from_region(DirtyRegion {
+                    columns: ((x0 / 2) as u8, (x1 / 2) as u8),
+                    rows,
+                }),
+            ];
+            for window in windows {
+                // Every dirty pixel is covered
+                prop_assert!(window.first_byte * 2 <= x0);
+                prop_assert!((window.first_byte + window.num_bytes) * 2 > x1);
+                prop_assert_eq!(window.rows, rows);
+
+                // Whole column addresses inside the framebuffer row
+                prop_assert_eq!(window.first_byte % BYTES_PER_COLUMN, 0);
+                prop_assert_eq!(window.num_bytes % BYTES_PER_COLUMN, 0);
+                prop_assert!(window.first_byte + window.num_bytes <= row_bytes);
+
+                // No write crosses the panel edge and each column address gets its bytes
+                let (first, last) = window.columns(range);
+                prop_assert!(range.0 <= first && last <= range.1);
+                prop_assert_eq!(usize::from(last - first + 1) * BYTES_PER_COLUMN, window.num_bytes);
+            }
+        }
+    }
+}
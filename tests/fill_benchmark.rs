@@ -0,0 +1,86 @@
+//! Compares the packed `fill_solid`/`fill_contiguous` paths with drawing pixel by pixel.
+//!
+//! Run with `cargo test --release --test fill_benchmark -- --ignored --nocapture`.
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::{pixelcolor::Gray4, prelude::*, primitives::Rectangle, Pixel};
+use ssd1322_di::display::Ssd1322;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 200;
+
+/// Discards everything sent to it.
+struct NullInterface;
+
+impl WriteOnlyDataCommand for NullInterface {
+    fn send_commands(&mut self, _cmds: DataFormat<'_>) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    fn send_data(&mut self, _buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        Ok(())
+    }
+}
+
+/// Runs `draw` with a different frame number each iteration and returns the average time.
+fn measure(mut draw: impl FnMut(&mut Ssd1322<NullInterface>, u32)) -> Duration {
+    let mut disp = Ssd1322::new(NullInterface);
+    let start = Instant::now();
+    for frame in 0..ITERATIONS {
+        draw(&mut disp, frame);
+        disp.flush().unwrap();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn report(name: &str, slow: Duration, fast: Duration) {
+    println!(
+        "{}: draw_iter {:?}, packed {:?}, speedup {:.1}x",
+        name,
+        slow,
+        fast,
+        slow.as_secs_f64() / fast.as_secs_f64()
+    );
+}
+
+/// Color of pixel `i` of an image in `frame`.
+fn image_color(i: u32, frame: u32) -> Gray4 {
+    Gray4::new(((i + frame) % 16) as u8)
+}
+
+#[test]
+#[ignore]
+fn full_screen_fill() {
+    let area = Rectangle::new(Point::zero(), Size::new(256, 64));
+
+    let slow = measure(|disp, frame| {
+        let color = Gray4::new((frame % 16) as u8);
+        disp.draw_iter(area.points().map(|p| Pixel(p, color)))
+            .unwrap();
+    });
+    let fast = measure(|disp, frame| {
+        disp.fill_solid(&area, Gray4::new((frame % 16) as u8))
+            .unwrap();
+    });
+
+    report("full screen fill", slow, fast);
+}
+
+#[test]
+#[ignore]
+fn image_blit() {
+    let area = Rectangle::new(Point::zero(), Size::new(256, 64));
+
+    let slow = measure(|disp, frame| {
+        let pixels = area
+            .points()
+            .zip(0..)
+            .map(|(p, i)| Pixel(p, image_color(i, frame)));
+        disp.draw_iter(pixels).unwrap();
+    });
+    let fast = measure(|disp, frame| {
+        let colors = (0..).map(|i| image_color(i, frame));
+        disp.fill_contiguous(&area, colors).unwrap();
+    });
+
+    report("256x64 image blit", slow, fast);
+}
@@ -0,0 +1,323 @@
+//! Checks the bytes sent by the driver and the resulting panel contents with `MockInterface`.
+use display_interface::{DataFormat, WriteOnlyDataCommand};
+use embedded_graphics::{
+    pixelcolor::Gray4,
+    prelude::*,
+    primitives::{Circle, PrimitiveStyle, Rectangle},
+};
+use proptest::prelude::*;
+use ssd1322_di::burnin::BurnInProtection;
+use ssd1322_di::command::Command;
+use ssd1322_di::direct::DirectSsd1322;
+use ssd1322_di::display::Ssd1322;
+use ssd1322_di::mock::{MockInterface, Transfer};
+use ssd1322_di::rotation::DisplayRotation;
+use ssd1322_di::size::{DisplaySize, DisplaySize256x64};
+
+/// 128x64 panel in the middle of the GDDRAM, exercising a column offset other than the default.
+struct Panel128x64;
+
+impl DisplaySize for Panel128x64 {
+    const WIDTH: u16 = 128;
+    const HEIGHT: u16 = 64;
+    const COLUMN_OFFSET: u8 = 0x2C;
+    type Buffer = [u8; 128 * 64 / 2];
+
+    fn new_buffer() -> Self::Buffer {
+        [0; 128 * 64 / 2]
+    }
+}
+
+fn draw_scene<T: DrawTarget<Color = Gray4>>(target: &mut T) -> Result<(), T::Error> {
+    Rectangle::new(Point::new(3, 5), Size::new(41, 7))
+        .into_styled(PrimitiveStyle::with_fill(Gray4::new(6)))
+        .draw(target)?;
+    Circle::new(Point::new(30, 20), 31)
+        .into_styled(PrimitiveStyle::with_stroke(Gray4::WHITE, 3))
+        .draw(target)
+}
+
+#[test]
+fn init_sends_the_configuration() {
+    let mut disp = Ssd1322::new(MockInterface::new());
+    disp.init().unwrap();
+
+    let commands = disp.interface().commands();
+    assert_eq!(
+        commands[..5],
+        [
+            (0xFD, vec![0x12]),
+            (0xAE, vec![]),
+            (0x15, vec![0x1C, 0x5B]),
+            (0x75, vec![0x00, 0x3F]),
+            (0xB3, vec![0x91]),
+        ]
+    );
+    assert_eq!(commands.last(), Some(&(0xAF, vec![])));
+    assert_eq!(
+        disp.interface().transfers()[..2],
+        [Transfer::Command(vec![0xFD]), Transfer::Data(vec![0x12])]
+    );
+}
+
+#[test]
+fn flush_sends_the_changed_window() {
+    let mut disp = Ssd1322::new(MockInterface::new());
+    disp.init().unwrap();
+    disp.flush_all().unwrap();
+    disp.interface_mut().clear_transfers();
+
+    // Pixels 5..=9 of rows 2..=3, widened to the column addresses covering pixels 4..=11
+    Rectangle::new(Point::new(5, 2), Size::new(5, 2))
+        .into_styled(PrimitiveStyle::with_fill(Gray4::WHITE))
+        .draw(&mut disp)
+        .unwrap();
+    disp.flush().unwrap();
+
+    let row = vec![0x0F, 0xFF, 0xFF, 0x00];
+    assert_eq!(
+        disp.interface().transfers(),
+        [
+            Transfer::Command(vec![0x15]),
+            Transfer::Data(vec![0x1D, 0x1E]),
+            Transfer::Command(vec![0x75]),
+            Transfer::Data(vec![2, 3]),
+            Transfer::Command(vec![0x5C]),
+            Transfer::Data(row.clone()),
+            Transfer::Data(row),
+        ]
+    );
+    assert_eq!(
+        disp.interface().panel::<DisplaySize256x64>(),
+        disp.snapshot()
+    );
+}
+
+#[test]
+fn panel_matches_framebuffer() {
+    let mut disp = Ssd1322::with_size(MockInterface::new(), Panel128x64);
+    disp.init().unwrap();
+    draw_scene(&mut disp).unwrap();
+    disp.flush().unwrap();
+    assert_eq!(disp.interface().panel::<Panel128x64>(), disp.snapshot());
+
+    // Scrolling moves the start line, the rows scrolled in are sent by the next flush
+    disp.scroll_to(20).unwrap();
+    draw_scene(&mut disp).unwrap();
+    disp.flush().unwrap();
+    assert_eq!(disp.interface().panel::<Panel128x64>(), disp.snapshot());
+}
+
+#[test]
+fn init_rewrites_scrolled_rows() {
+    let mut disp = Ssd1322::new(MockInterface::new());
+    disp.init().unwrap();
+    draw_scene(&mut disp).unwrap();
+    disp.scroll_to(20).unwrap();
+    disp.flush().unwrap();
+
+    // Init moves the start line back, the next flush puts the rows back in place
+    disp.init().unwrap();
+    assert_eq!(disp.scroll_position(), 0);
+    disp.flush().unwrap();
+    assert_eq!(
+        disp.interface().panel::<DisplaySize256x64>(),
+        disp.snapshot()
+    );
+}
+
+#[test]
+fn init_keeps_the_back_buffer_in_step() {
+    let mut disp = Ssd1322::new(MockInterface::new());
+    disp.init().unwrap();
+    disp.enable_double_buffering(Box::leak(vec![0; 256 * 64 / 2].into_boxed_slice()))
+        .unwrap();
+    draw_scene(&mut disp).unwrap();
+    disp.present().unwrap();
+    disp.scroll_to(20).unwrap();
+    draw_aligned(&mut disp).unwrap();
+
+    // The frame drawn before the init is presented at the reset scroll position
+    disp.init().unwrap();
+    disp.present().unwrap();
+    let presented = disp.snapshot();
+    assert_eq!(disp.interface().panel::<DisplaySize256x64>(), presented);
+
+    // Nothing drawn is left unpresented
+    disp.disable_double_buffering();
+    assert_eq!(disp.snapshot(), presented);
+}
+
+#[test]
+fn rotation_is_applied_by_the_remap() {
+    let mut disp = Ssd1322::new(MockInterface::new());
+    disp.init().unwrap();
+    disp.set_rotation(DisplayRotation::Rotate180).unwrap();
+    draw_scene(&mut disp).unwrap();
+    disp.flush().unwrap();
+
+    let panel = disp.interface().panel::<DisplaySize256x64>();
+    let snapshot = disp.snapshot();
+    for y in 0..64 {
+        for x in 0..256 {
+            assert_eq!(panel.pixel(255 - x, 63 - y), snapshot.pixel(x, y));
+        }
+    }
+}
+
+#[test]
+fn burn_in_shift_shows_blank_rows() {
+    let mut disp = Ssd1322::new(MockInterface::new());
+    // Power-on garbage in the whole GDDRAM
+    for command in [
+        Command::SetColumnAddress(0, 119),
+        Command::SetRowAddress(0, 127),
+        Command::WriteRAM,
+    ] {
+        disp.send_command(command).unwrap();
+    }
+    disp.interface_mut()
+        .send_data(DataFormat::U8(&vec![0xFF; 240 * 128]))
+        .unwrap();
+
+    disp.init().unwrap();
+    draw_scene(&mut disp).unwrap();
+    disp.flush_all().unwrap();
+    disp.enable_burn_in_protection(BurnInProtection {
+        shift_interval_ms: 1,
+        max_shift: 4,
+        idle_timeout_ms: None,
+        ..BurnInProtection::default()
+    });
+
+    // The image moves down, showing black rows above it, also after scrolling while shifted
+    let check = |disp: &mut Ssd1322<MockInterface>, shift: u32| {
+        disp.tick(1).unwrap();
+        let panel = disp.interface().panel::<DisplaySize256x64>();
+        let snapshot = disp.snapshot();
+        for y in 0..64u32 {
+            for x in 0..256 {
+                let expected = y
+                    .checked_sub(shift)
+                    .map_or(Some(0), |y| snapshot.pixel(x, y));
+                assert_eq!(
+                    panel.pixel(x, y),
+                    expected,
+                    "shift {} at {}, {}",
+                    shift,
+                    x,
+                    y
+                );
+            }
+        }
+    };
+    for shift in 1..=4 {
+        check(&mut disp, shift);
+    }
+    disp.scroll_by(10).unwrap();
+    disp.flush().unwrap();
+    check(&mut disp, 3);
+    disp.scroll_by(-30).unwrap();
+    disp.flush().unwrap();
+    check(&mut disp, 2);
+}
+
+/// Draws an image clipped at the left edge and a bar, both covering whole column addresses.
+fn draw_aligned<T: DrawTarget<Color = Gray4>>(target: &mut T) -> Result<(), T::Error> {
+    let image = Rectangle::new(Point::new(-4, 10), Size::new(24, 6));
+    target.fill_contiguous(&image, (0..).map(|i| Gray4::new(i % 16)))?;
+    let bar = Rectangle::new(Point::new(40, 30), Size::new(48, 40));
+    target.fill_solid(&bar, Gray4::new(7))
+}
+
+#[test]
+fn direct_drawing_matches_framebuffer() {
+    let mut direct = DirectSsd1322::with_size(MockInterface::new(), Panel128x64);
+    direct.init().unwrap();
+    draw_aligned(&mut direct).unwrap();
+
+    let mut disp = Ssd1322::with_size(MockInterface::new(), Panel128x64);
+    disp.init().unwrap();
+    draw_aligned(&mut disp).unwrap();
+    disp.flush().unwrap();
+
+    assert_eq!(direct.interface().panel::<Panel128x64>(), disp.snapshot());
+}
+
+/// Draws unaligned shapes over an aligned background, so edge column addresses hold pixels
+/// drawn before.
+fn draw_unaligned<T: DrawTarget<Color = Gray4>>(target: &mut T) -> Result<(), T::Error> {
+    let background = Rectangle::new(Point::new(0, 0), Size::new(64, 64));
+    target.fill_contiguous(&background, (0..7).cycle().map(Gray4::new))?;
+    draw_scene(target)?;
+    let image = Rectangle::new(Point::new(-3, 40), Size::new(10, 5));
+    target.fill_contiguous(&image, (0..16).rev().cycle().map(Gray4::new))?;
+    Pixel(Point::new(126, 63), Gray4::WHITE).draw(target)
+}
+
+#[test]
+fn direct_drawing_reads_back_partial_columns() {
+    let mut direct = DirectSsd1322::with_readback(MockInterface::new(), Panel128x64);
+    direct.init().unwrap();
+    draw_unaligned(&mut direct).unwrap();
+
+    let mut disp = Ssd1322::with_size(MockInterface::new(), Panel128x64);
+    disp.init().unwrap();
+    draw_unaligned(&mut disp).unwrap();
+    disp.flush().unwrap();
+
+    assert_eq!(direct.interface().panel::<Panel128x64>(), disp.snapshot());
+    assert!(direct
+        .interface()
+        .transfers()
+        .iter()
+        .any(|transfer| matches!(transfer, Transfer::Read(bytes) if bytes.len() == 3)));
+}
+
+/// Random filled rectangles, partly outside the panel.
+fn rectangles() -> impl Strategy<Value = Vec<(Rectangle, u8)>> {
+    let rectangle = (-8..136i32, -8..72i32, 0..40u32, 0..24u32, 0..16u8)
+        .prop_map(|(x, y, w, h, luma)| (Rectangle::new(Point::new(x, y), Size::new(w, h)), luma));
+    proptest::collection::vec(rectangle, 1..12)
+}
+
+proptest! {
+    #[test]
+    fn flush_writes_every_change_inside_the_panel(
+        rectangles in rectangles(),
+        rotated in any::<bool>(),
+    ) {
+        let rotation = if rotated { DisplayRotation::Rotate180 } else { DisplayRotation::Rotate0 };
+        let mut disp = Ssd1322::with_size(MockInterface::new(), Panel128x64);
+        disp.init().unwrap();
+        disp.set_rotation(rotation).unwrap();
+        for (i, (area, luma)) in rectangles.iter().enumerate() {
+            disp.fill_solid(area, Gray4::new(*luma)).unwrap();
+            if i % 3 == 2 {
+                disp.flush().unwrap();
+            }
+        }
+        disp.flush().unwrap();
+
+        let mut reference = Ssd1322::with_size(MockInterface::new(), Panel128x64);
+        reference.init().unwrap();
+        reference.set_rotation(rotation).unwrap();
+        for (area, luma) in &rectangles {
+            reference.fill_solid(area, Gray4::new(*luma)).unwrap();
+        }
+        reference.flush_all().unwrap();
+
+        let mock = disp.interface();
+        prop_assert_eq!(
+            mock.panel::<Panel128x64>(),
+            reference.interface().panel::<Panel128x64>()
+        );
+
+        // Column addresses outside the panel are never written
+        let (start, end) = Panel128x64::column_range(rotated);
+        for row in mock.gddram().chunks(240) {
+            prop_assert!(row[..usize::from(start) * 2].iter().all(|&byte| byte == 0));
+            prop_assert!(row[(usize::from(end) + 1) * 2..].iter().all(|&byte| byte == 0));
+        }
+    }
+}
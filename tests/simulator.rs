@@ -0,0 +1,85 @@
+//! Checks that the simulator renders the emulated panel with the display settings applied.
+use embedded_graphics::{
+    pixelcolor::{Gray4, Gray8},
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+};
+use embedded_graphics_simulator::OutputSettingsBuilder;
+use ssd1322_di::display::Ssd1322;
+use ssd1322_di::grayscale::GrayScaleTable;
+use ssd1322_di::mode::DisplayMode;
+use ssd1322_di::simulator::SimulatedSsd1322;
+
+/// Simulated display showing a black pixel at (0, 0), level 8 at (1, 0) and white at (2, 0).
+fn simulated() -> SimulatedSsd1322 {
+    let mut disp = Ssd1322::simulated();
+    disp.init().unwrap();
+    disp.set_brightness(255).unwrap();
+    for (x, level) in [(1, 8), (2, 15)] {
+        Rectangle::new(Point::new(x, 0), Size::new(1, 1))
+            .into_styled(PrimitiveStyle::with_fill(Gray4::new(level)))
+            .draw(&mut disp)
+            .unwrap();
+    }
+    disp.flush().unwrap();
+    disp
+}
+
+fn grays(disp: &SimulatedSsd1322) -> [u8; 3] {
+    let render = disp.render();
+    [0, 1, 2].map(|x| render.get_pixel(Point::new(x, 0)).luma())
+}
+
+#[test]
+fn renders_flushed_levels() {
+    let mut disp = simulated();
+    let [black, mid, white] = grays(&disp);
+    assert_eq!((black, white), (0, 255));
+    assert!(black < mid && mid < white);
+
+    // Unflushed changes are not shown
+    Pixel(Point::new(0, 0), Gray4::WHITE)
+        .draw(&mut disp)
+        .unwrap();
+    assert_eq!(grays(&disp)[0], 0);
+}
+
+#[test]
+fn honors_display_mode_and_power() {
+    let mut disp = simulated();
+    disp.set_display_mode(DisplayMode::Inverse).unwrap();
+    assert_eq!(grays(&disp)[0], 255);
+    assert_eq!(grays(&disp)[2], 0);
+
+    disp.set_display_mode(DisplayMode::AllOn).unwrap();
+    assert_eq!(grays(&disp), [255; 3]);
+
+    disp.sleep().unwrap();
+    assert_eq!(grays(&disp), [0; 3]);
+}
+
+#[test]
+fn honors_gray_scale_table_and_brightness() {
+    let mut disp = simulated();
+    let linear = grays(&disp);
+
+    disp.set_gray_scale_table(&GrayScaleTable::GAMMA_2_2)
+        .unwrap();
+    let gamma = grays(&disp);
+    assert!(gamma[1] < linear[1]);
+    assert_eq!(gamma[2], linear[2]);
+
+    disp.set_brightness(100).unwrap();
+    assert!(grays(&disp)[2] < linear[2]);
+}
+
+#[test]
+fn renders_headless_images() {
+    let disp = simulated();
+    let settings = OutputSettingsBuilder::new().scale(2).build();
+    let image = disp.render().to_grayscale_output_image(&settings);
+
+    let buffer = image.as_image_buffer();
+    assert_eq!(buffer.dimensions(), (512, 128));
+    assert_eq!(buffer.get_pixel(4, 0).0, [Gray8::WHITE.luma()]);
+}
@@ -0,0 +1,89 @@
+//! Compares rendered frames against the golden images in `tests/snapshots`.
+//!
+//! Run with `UPDATE_SNAPSHOTS=1 cargo test --features std --test snapshot` to create or rewrite
+//! them.
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::Gray4,
+    prelude::*,
+    primitives::{Circle, PrimitiveStyle, Rectangle},
+    text::Text,
+};
+use ssd1322_di::display::Ssd1322;
+use ssd1322_di::rotation::DisplayRotation;
+use ssd1322_di::size::DisplaySize;
+
+/// 128x64 panel in the middle of the GDDRAM, exercising a column offset other than the default.
+struct Panel128x64;
+
+impl DisplaySize for Panel128x64 {
+    const WIDTH: u16 = 128;
+    const HEIGHT: u16 = 64;
+    const COLUMN_OFFSET: u8 = 0x2C;
+    type Buffer = [u8; 128 * 64 / 2];
+
+    fn new_buffer() -> Self::Buffer {
+        [0; 128 * 64 / 2]
+    }
+}
+
+/// Discards everything sent to it.
+struct NullInterface;
+
+impl WriteOnlyDataCommand for NullInterface {
+    fn send_commands(&mut self, _cmds: DataFormat<'_>) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    fn send_data(&mut self, _buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        Ok(())
+    }
+}
+
+fn golden(name: &str) -> String {
+    format!(
+        "{}/tests/snapshots/{}.pgm",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    )
+}
+
+/// Draws a gray ramp, a circle and some text.
+fn draw_scene<T: DrawTarget<Color = Gray4>>(target: &mut T) -> Result<(), T::Error> {
+    for level in 0..16 {
+        Rectangle::new(Point::new(level * 4, 0), Size::new(4, 8))
+            .into_styled(PrimitiveStyle::with_fill(Gray4::new(level as u8)))
+            .draw(target)?;
+    }
+    Circle::new(Point::new(2, 12), 20)
+        .into_styled(PrimitiveStyle::with_stroke(Gray4::new(9), 2))
+        .draw(target)?;
+    Text::new(
+        "SSD1322",
+        Point::new(26, 24),
+        MonoTextStyle::new(&FONT_6X10, Gray4::WHITE),
+    )
+    .draw(target)?;
+
+    Ok(())
+}
+
+#[test]
+fn scene() {
+    let mut disp = Ssd1322::with_size(NullInterface, Panel128x64);
+    draw_scene(&mut disp).unwrap();
+
+    disp.snapshot().assert_matches(golden("scene"));
+}
+
+#[test]
+fn rotated_scene() {
+    let mut disp = Ssd1322::with_size(NullInterface, Panel128x64);
+    disp.set_rotation(DisplayRotation::Rotate90).unwrap();
+    draw_scene(&mut disp).unwrap();
+
+    let snapshot = disp.snapshot();
+    assert_eq!((snapshot.width(), snapshot.height()), (64, 128));
+    snapshot.assert_matches(golden("rotated_scene"));
+}